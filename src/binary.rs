@@ -0,0 +1,184 @@
+// for etc.
+use std::io::{self, Read};
+
+use crate::command::COMMANDS;
+
+// with binary framing (start 400 400 Title format=binary), everything after
+// the start line is a frame
+//
+// a frame is an opcode byte and then either
+// - for the opcode LINE, a little-endian u32 number of bytes and then a line
+//   of text (without the newline) that is handled like any other line
+// - for any other opcode, a little-endian u16 number of arguments and then
+//   each argument as a little-endian f32
+//
+// the opcode of a command is where it is in the list of commands (0 is start,
+// 1 is present, 2 is get, ...) and only commands whose arguments are all
+// numbers can be sent as numbers, anything else is sent as a line
+pub const LINE: u8 = 255;
+
+// what a frame was
+pub enum Frame {
+    // a line of text, which has been put into the line being read
+    Line(usize),
+    // a command with numbers for arguments, the opcode of the command
+    Numbers(usize),
+    // there is nothing more to read
+    End,
+}
+
+// read a frame, putting a line of text into buf or numbers into numbers
+pub fn read(reader: &mut dyn Read, buf: &mut String, numbers: &mut Vec<f32>) -> io::Result<Frame> {
+    let mut opcode = [0; 1];
+    if reader.read(&mut opcode)? == 0 {
+        return Ok(Frame::End);
+    }
+
+    if opcode[0] == LINE {
+        let mut length = [0; 4];
+        reader.read_exact(&mut length)?;
+        let mut line = vec![0; u32::from_le_bytes(length) as usize];
+        reader.read_exact(&mut line)?;
+        let line = String::from_utf8(line)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "line isn't UTF-8"))?;
+        buf.push_str(&line);
+        buf.push('\n');
+        return Ok(Frame::Line(line.len() + 1));
+    }
+
+    let mut count = [0; 2];
+    reader.read_exact(&mut count)?;
+    numbers.clear();
+    for _ in 0..u16::from_le_bytes(count) {
+        let mut number = [0; 4];
+        reader.read_exact(&mut number)?;
+        numbers.push(f32::from_le_bytes(number));
+    }
+    Ok(Frame::Numbers(opcode[0] as usize))
+}
+
+// the line a command sent as numbers would have been without binary framing
+pub fn line(opcode: usize, numbers: &[f32]) -> String {
+    let mut line = COMMANDS
+        .get(opcode)
+        .map_or("?", |(name, _)| *name)
+        .to_string();
+    for number in numbers {
+        line.push(' ');
+        line.push_str(&number.to_string());
+    }
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Command, Error};
+
+    fn opcode(name: &str) -> usize {
+        COMMANDS
+            .iter()
+            .position(|(command, _)| *command == name)
+            .unwrap()
+    }
+
+    fn line_frame(line: &str) -> Vec<u8> {
+        let mut frame = vec![LINE];
+        frame.extend((line.len() as u32).to_le_bytes());
+        frame.extend(line.as_bytes());
+        frame
+    }
+
+    fn numbers_frame(opcode: usize, numbers: &[f32]) -> Vec<u8> {
+        let mut frame = vec![opcode as u8];
+        frame.extend((numbers.len() as u16).to_le_bytes());
+        for number in numbers {
+            frame.extend(number.to_le_bytes());
+        }
+        frame
+    }
+
+    #[test]
+    fn lines() {
+        let mut frames = line_frame("print héllo  there");
+        frames.extend(line_frame(""));
+        let mut reader = &frames[..];
+        let mut buf = String::new();
+        let mut numbers = vec![];
+
+        let frame = read(&mut reader, &mut buf, &mut numbers).unwrap();
+        assert!(matches!(frame, Frame::Line(read) if read == buf.len()));
+        assert_eq!(buf, "print héllo  there\n");
+        buf.clear();
+        assert!(matches!(
+            read(&mut reader, &mut buf, &mut numbers).unwrap(),
+            Frame::Line(1)
+        ));
+        assert_eq!(buf, "\n");
+        assert!(matches!(
+            read(&mut reader, &mut buf, &mut numbers).unwrap(),
+            Frame::End
+        ));
+    }
+
+    #[test]
+    fn numbers() {
+        let frames = numbers_frame(opcode("rect"), &[1.0, 2.5, -3.0, 4.0]);
+        let mut buf = String::new();
+        let mut numbers = vec![];
+        let frame = read(&mut &frames[..], &mut buf, &mut numbers).unwrap();
+
+        assert!(matches!(frame, Frame::Numbers(read) if read == opcode("rect")));
+        assert!(buf.is_empty());
+        assert_eq!(numbers, [1.0, 2.5, -3.0, 4.0]);
+        assert_eq!(line(opcode("rect"), &numbers), "rect 1 2.5 -3 4\n");
+        assert!(matches!(
+            Command::decode(opcode("rect"), &numbers),
+            Ok(Command::Rect(x, y, w, h)) if (x, y, w, h) == (1.0, 2.5, -3.0, 4.0)
+        ));
+    }
+
+    #[test]
+    fn wrong_arity() {
+        assert!(matches!(
+            Command::decode(opcode("rect"), &[1.0, 2.0, 3.0]),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            Command::decode(opcode("poly"), &[1.0, 2.0, 3.0]),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            Command::decode(opcode("fill"), &[1.0, 2.0, 3.0, 4.0]),
+            Ok(Command::Fill(1, 2, 3, 4))
+        ));
+        // commands with words that aren't numbers have to be sent as lines
+        assert!(matches!(
+            Command::decode(opcode("save"), &[]),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            Command::decode(LINE as usize, &[]),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf = String::new();
+        let mut numbers = vec![];
+        for frames in [
+            numbers_frame(opcode("rect"), &[1.0, 2.0, 3.0, 4.0]),
+            line_frame("rect 1 2 3 4"),
+        ] {
+            for end in 1..frames.len() {
+                let read = read(&mut &frames[..end], &mut buf, &mut numbers);
+                assert_eq!(
+                    read.err().map(|error| error.kind()),
+                    Some(io::ErrorKind::UnexpectedEof)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// for drawing graphics
+use raqote::{
+    DrawOptions, DrawTarget, Image, LineCap, LineJoin, Path, PathBuilder, Point, SolidSource,
+    Source, StrokeStyle, Transform,
+};
+// for computing transformations
+use euclid::Angle;
+// for fonts
+use font_kit::loaders::default::Font;
+// for etc.
+use std::collections::HashMap;
+use std::fs::File;
+
+use crate::command::Command;
+
+// a canvas is everything we draw to along with the state (fill, stroke,
+// transformations, etc.) that commands change
+//
+// it doesn't know anything about windows or processes, so the same canvas can
+// be drawn to by a client in a window or by a recorded session with no
+// window at all
+pub struct Canvas {
+    pub draw_target: DrawTarget,
+    fill: Option<(u8, u8, u8, u8)>,
+    stroke: Option<(u8, u8, u8, u8)>,
+    stroke_weight: f32,
+    stroke_cap: LineCap,
+    stroke_join: LineJoin,
+    transformations: Vec<Transform>,
+    text_font: Option<Font>,
+    text_size: Option<f32>,
+    images: HashMap<String, (u32, u32, Vec<u32>)>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Canvas {
+        let draw_target = DrawTarget::new(width as i32, height as i32);
+        let transformations = vec![*draw_target.get_transform()];
+
+        Canvas {
+            draw_target,
+            fill: None,
+            stroke: Some((0, 0, 0, 255)),
+            stroke_weight: 1.0,
+            stroke_cap: LineCap::Round,
+            stroke_join: LineJoin::Miter,
+            transformations,
+            text_font: None,
+            text_size: None,
+            images: HashMap::new(),
+        }
+    }
+
+    // the pixels to present
+    pub fn data(&self) -> &[u32] {
+        self.draw_target.get_data()
+    }
+
+    // the pixels as red-green-blue-alpha bytes, exactly as they are saved by
+    // the save command
+    pub fn rgba(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.data().len() * 4);
+        for pixel in self.data() {
+            let a = (pixel >> 24) & 0xff;
+
+            // undo premultiplied alpha (pixels with no alpha are left as is)
+            let unpremultiply = |channel: u32| (channel * 255).checked_div(a).unwrap_or(channel);
+            let r = unpremultiply((pixel >> 16) & 0xff);
+            let g = unpremultiply((pixel >> 8) & 0xff);
+            let b = unpremultiply(pixel & 0xff);
+
+            output.extend_from_slice(&[r as u8, g as u8, b as u8, a as u8]);
+        }
+        output
+    }
+
+    // handle a command that draws something or changes what will be drawn
+    //
+    // other commands (present, get, text) are up to whoever is running the
+    // canvas and are ignored here
+    pub fn execute(&mut self, command: &Command) {
+        match command {
+            Command::NoFill => {
+                self.fill = None;
+            }
+            Command::NoStroke => {
+                self.stroke = None;
+            }
+            Command::Push => {
+                if self.transformations.is_empty() {
+                    panic!("invalid usage of push and pop");
+                }
+                self.transformations
+                    .push(*self.transformations.last().unwrap());
+            }
+            Command::Pop => {
+                // the transformation from before the first push is never
+                // popped, so that a pop that wasn't expected can be carried on
+                // from (like with --json)
+                match self.transformations.len() {
+                    0 => panic!("no transformation to pop"),
+                    1 => panic!("pop was not expected"),
+                    _ => {}
+                }
+                self.transformations.pop();
+                self.draw_target
+                    .set_transform(self.transformations.last().unwrap());
+            }
+            Command::Save(file) => {
+                self.draw_target.write_png(file).expect("failed to save");
+            }
+            Command::StrokeWeight(weight) => {
+                self.stroke_weight = *weight;
+            }
+            Command::StrokeCap(cap) => {
+                self.stroke_cap = *cap;
+            }
+            Command::StrokeJoin(join) => {
+                self.stroke_join = *join;
+            }
+            Command::Rotate(degrees) => {
+                self.transform(&Transform::create_rotation(Angle::degrees(*degrees)));
+            }
+            Command::TextFont(file) => {
+                self.text_font = Some(Font::from_path(file, 0).expect("failed to load font"));
+            }
+            Command::TextSize(size) => {
+                self.text_size = Some(*size);
+            }
+            Command::Translate(x, y) => {
+                self.transform(&Transform::create_translation(*x, *y));
+            }
+            Command::Scale(x, y) => {
+                self.transform(&Transform::create_scale(*x, *y));
+            }
+            Command::Open(file, name) => {
+                let decoder = png::Decoder::new(File::open(file).expect("failed to open file"));
+                let (info, mut reader) = decoder.read_info().expect("failed to open file");
+                let width = info.width;
+                let height = info.height;
+                let mut buf = vec![0; info.buffer_size()];
+                reader.next_frame(&mut buf).unwrap();
+                let buf_usize = buf.iter().map(|byte| *byte as u32).collect::<Vec<u32>>();
+                self.images.insert(name.clone(), (width, height, buf_usize));
+            }
+            Command::Image(name, x, y, size) => {
+                let (width, height, data) = self.images.get(name).expect("image not opened yet");
+                let image = Image {
+                    width: *width as i32,
+                    height: *height as i32,
+                    data,
+                };
+                match size {
+                    Some((w, h)) => self.draw_target.draw_image_with_size_at(
+                        *w,
+                        *h,
+                        *x,
+                        *y,
+                        &image,
+                        &DrawOptions::default(),
+                    ),
+                    None => self
+                        .draw_target
+                        .draw_image_at(*x, *y, &image, &DrawOptions::default()),
+                }
+            }
+            Command::Background(r, g, b) => {
+                self.draw_target.clear(SolidSource {
+                    r: *r,
+                    g: *g,
+                    b: *b,
+                    a: 0,
+                });
+            }
+            Command::Fill(r, g, b, a) => {
+                self.fill = Some((*r, *g, *b, *a));
+            }
+            Command::Stroke(r, g, b, a) => {
+                self.stroke = Some((*r, *g, *b, *a));
+            }
+            Command::Circle(x, y, r) => {
+                let mut pb = PathBuilder::new();
+                pb.move_to(x + r, *y);
+                pb.arc(*x, *y, *r, 0.0, 2.0 * std::f32::consts::PI);
+                let path = pb.finish();
+
+                self.stroke_path(&path);
+            }
+            Command::Ellipse(x, y, w, h) => {
+                // push scaling transform
+                self.transformations
+                    .push(*self.transformations.last().unwrap());
+
+                // scale
+                self.transform(&Transform::create_scale(1.0, h / w));
+
+                // draw the ellipse
+                let mut pb = PathBuilder::new();
+                pb.move_to(x + w / 2.0, *y);
+                pb.arc(*x, *y, w / 2.0, 0.0, 2.0 * std::f32::consts::PI);
+                let path = pb.finish();
+
+                self.stroke_path(&path);
+
+                // pop scaling tansform
+                self.transformations.pop();
+                self.draw_target
+                    .set_transform(self.transformations.last().unwrap());
+            }
+            Command::Line(x1, y1, x2, y2) => {
+                let mut pb = PathBuilder::new();
+                pb.move_to(*x1, *y1);
+                pb.line_to(*x2, *y2);
+                let path = pb.finish();
+
+                self.fill_path(&path);
+                self.stroke_path(&path);
+            }
+            Command::Rect(x, y, w, h) => {
+                let mut pb = PathBuilder::new();
+                pb.rect(*x, *y, *w, *h);
+                let path = pb.finish();
+
+                self.fill_path(&path);
+                self.stroke_path(&path);
+            }
+            Command::Arc(x, y, r, start_angle, end_angle) => {
+                let mut pb = PathBuilder::new();
+                pb.move_to(x + r * start_angle.cos(), y + r * start_angle.sin());
+                pb.arc(
+                    *x,
+                    *y,
+                    *r,
+                    *start_angle,
+                    2.0 * std::f32::consts::PI * end_angle / 360.0,
+                );
+                let path = pb.finish();
+
+                self.fill_path(&path);
+                self.stroke_path(&path);
+            }
+            Command::Poly(points) => {
+                let mut pb = PathBuilder::new();
+                for (i, (x, y)) in points.iter().enumerate() {
+                    if i == 0 {
+                        pb.move_to(*x, *y);
+                    } else {
+                        pb.line_to(*x, *y);
+                    }
+                }
+                let path = pb.finish();
+
+                self.fill_path(&path);
+                self.stroke_path(&path);
+            }
+            Command::Present
+            | Command::PresentForever
+            | Command::Get(_)
+            | Command::Text(..)
+            | Command::Print(_)
+            | Command::Set(..)
+            | Command::Repeat(_)
+            | Command::For(..)
+            | Command::Define(..)
+            | Command::End
+            | Command::Layer(..)
+            | Command::Window(..)
+            | Command::Select(..)
+            | Command::CloseCancel
+            | Command::Events(_) => {}
+        }
+    }
+
+    // draw a line of text at the given position with the current fill
+    pub fn text(&mut self, x: f32, y: f32, text: &str) {
+        if let Some(fill_color) = self.fill {
+            let source = Source::Solid(SolidSource {
+                r: fill_color.0,
+                g: fill_color.1,
+                b: fill_color.2,
+                a: 255,
+            });
+            self.draw_target.draw_text(
+                self.text_font
+                    .as_ref()
+                    .expect("text font must be given before text is drawn"),
+                self.text_size
+                    .expect("text size must be given before text is drawn"),
+                text.split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+                    .as_str(),
+                Point::new(x, y),
+                &source,
+                &DrawOptions {
+                    alpha: fill_color.3 as f32 / 255.0,
+                    ..DrawOptions::default()
+                },
+            );
+        }
+    }
+
+    // apply a transformation on top of the current one
+    fn transform(&mut self, transform: &Transform) {
+        if self.transformations.is_empty() {
+            panic!("invalid usage of push and pop");
+        }
+        let last = self.transformations.last_mut().unwrap();
+        *last = last.post_transform(transform);
+        self.draw_target.set_transform(last);
+    }
+
+    fn fill_path(&mut self, path: &Path) {
+        if let Some(fill_color) = &self.fill {
+            let source = Source::Solid(SolidSource {
+                r: fill_color.0,
+                g: fill_color.1,
+                b: fill_color.2,
+                a: 255,
+            });
+            self.draw_target.fill(
+                path,
+                &source,
+                &DrawOptions {
+                    alpha: fill_color.3 as f32 / 255.0,
+                    ..DrawOptions::default()
+                },
+            );
+        }
+    }
+
+    fn stroke_path(&mut self, path: &Path) {
+        if let Some(stroke_color) = &self.stroke {
+            let source = Source::Solid(SolidSource {
+                r: stroke_color.0,
+                g: stroke_color.1,
+                b: stroke_color.2,
+                a: stroke_color.3,
+            });
+            self.draw_target.stroke(
+                path,
+                &source,
+                &StrokeStyle {
+                    width: self.stroke_weight,
+                    cap: self.stroke_cap,
+                    join: self.stroke_join,
+                    ..StrokeStyle::default()
+                },
+                &DrawOptions::default(),
+            );
+        }
+    }
+}
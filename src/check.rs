@@ -0,0 +1,425 @@
+// for etc.
+use std::collections::{HashMap, HashSet};
+
+use crate::command::{self, Command, Error, Start};
+use crate::expression::{self, Variables};
+use crate::include::{self, Includes};
+
+// stdg check FILE
+//
+// look for mistakes in a script of commands without running it
+//
+// every line is parsed exactly like it would be when running the script, so
+// anything reported here is something that would go wrong (or, for warnings,
+// probably isn't what was meant) when the script is run
+pub fn main(args: &[String]) {
+    let path = args.first().expect("expected script to check");
+    let mut errors = 0;
+    let mut warnings = 0;
+    for (location, severity, message) in check(path) {
+        println!("{}: {}: {}", location, severity, message);
+        match severity {
+            "error" => errors += 1,
+            _ => warnings += 1,
+        }
+    }
+
+    println!("{} errors, {} warnings", errors, warnings);
+    if errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+// the problems with a script as where they are (file:line), severity (error
+// or warning) and message
+fn check(path: &str) -> Vec<(String, &'static str, String)> {
+    let mut problems = vec![];
+    let mut includes = Includes::new(None);
+    includes.open(path).expect("failed to read script");
+
+    // the first line has to be the start command
+    match next_line(&mut includes, &mut problems) {
+        Some((location, line)) => {
+            let line = command::strip_comment(&line);
+            let line = command::strip_prefix(line, command::PREFIX, false).unwrap_or(line);
+            if let Err(message) = Start::parse(line) {
+                problems.push((location, "error", message));
+            }
+        }
+        None => problems.push((
+            format!("{}:1", path),
+            "error",
+            "expected start command".to_string(),
+        )),
+    }
+
+    // keep track of what has been done so far
+    let mut pushes = vec![];
+    let mut images = HashSet::new();
+    let mut text_font = false;
+    let mut text_size = false;
+    let mut variables = Variables::new();
+    // blocks that haven't ended yet and the number of arguments of commands
+    // made with define
+    let mut blocks = vec![];
+    let mut defines = HashMap::new();
+
+    while let Some((location, line)) = next_line(&mut includes, &mut problems) {
+        let command = expression::words(
+            command::strip_prefix(command::strip_comment(&line), command::PREFIX, false)
+                .unwrap_or(""),
+        );
+        // only the arguments of commands that are numbers are worked out,
+        // like when running
+        let name = command.first().cloned().unwrap_or_default();
+        let is_number =
+            |position| defines.contains_key(&name) || command::is_number(&name, position);
+        let command = match variables.expand(&command, is_number) {
+            Ok(command) => command,
+            Err(message) => {
+                problems.push((location, "error", message));
+                continue;
+            }
+        };
+
+        match Command::parse(&command) {
+            Ok(Command::Push) => pushes.push(location),
+            Ok(Command::Pop) => {
+                if pushes.pop().is_none() {
+                    problems.push((location.clone(), "error", "pop without push".to_string()));
+                }
+            }
+            Ok(Command::Open(_, name)) => {
+                images.insert(name);
+            }
+            Ok(Command::Image(name, ..)) => {
+                if !images.contains(&name) {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        format!("image `{}` is drawn before it is opened", name),
+                    ));
+                }
+            }
+            Ok(Command::Set(name, value)) => variables.set(&name, value),
+            // the lines of a block are checked once, as if it ran once
+            Ok(Command::Repeat(_)) => blocks.push(location),
+            Ok(Command::For(name, from, ..)) => {
+                variables.set(&name, from);
+                blocks.push(location);
+            }
+            Ok(Command::Define(name, arguments)) => {
+                for argument in &arguments {
+                    variables.set(argument, 0.0);
+                }
+                defines.insert(name, arguments.len());
+                blocks.push(location);
+            }
+            Ok(Command::End) => {
+                if blocks.pop().is_none() {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        "end without repeat, for or define".to_string(),
+                    ));
+                }
+            }
+            Ok(Command::TextFont(_)) => text_font = true,
+            Ok(Command::TextSize(_)) => text_size = true,
+            Ok(Command::Text(..)) => {
+                if !text_font {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        "text before textfont".to_string(),
+                    ));
+                }
+                if !text_size {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        "text before textsize".to_string(),
+                    ));
+                }
+                // the next line is the text to draw, not a command
+                if includes.read_line(&mut String::new()).is_none() {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        "text must be followed by line with text".to_string(),
+                    ));
+                }
+            }
+            Ok(_) => {}
+            Err(Error::Unknown)
+                if command
+                    .first()
+                    .is_some_and(|name| defines.contains_key(name)) =>
+            {
+                if defines[&command[0]] != command.len() - 1 {
+                    problems.push((
+                        location.clone(),
+                        "error",
+                        format!(
+                            "expected {} arguments to {} but got {}",
+                            defines[&command[0]],
+                            command[0],
+                            command.len() - 1
+                        ),
+                    ));
+                }
+            }
+            Err(Error::Unknown) => {
+                if !command.is_empty() {
+                    problems.push((
+                        location.clone(),
+                        "warning",
+                        format!("`{}` is not a command, line will be printed", command[0]),
+                    ));
+                }
+            }
+            Err(Error::Arity(_)) if command[0] == "start" => {
+                problems.push((
+                    location.clone(),
+                    "error",
+                    "start must only be the first line, line will be printed".to_string(),
+                ));
+            }
+            Err(Error::Arity(usage)) => {
+                problems.push((
+                    location.clone(),
+                    "error",
+                    format!(
+                        "expected `{}` but got {} arguments, line will be printed",
+                        usage,
+                        command.len() - 1
+                    ),
+                ));
+            }
+            Err(Error::Invalid(message)) => problems.push((location, "error", message)),
+        }
+    }
+
+    for location in blocks {
+        problems.push((
+            location,
+            "error",
+            "repeat, for or define without end".to_string(),
+        ));
+    }
+    for location in pushes {
+        problems.push((location, "error", "push without pop".to_string()));
+    }
+
+    problems
+}
+
+// the next line of the script and where it is (file:line), following include
+// commands into the files they include
+fn next_line(
+    includes: &mut Includes,
+    problems: &mut Vec<(String, &'static str, String)>,
+) -> Option<(String, String)> {
+    loop {
+        let mut line = String::new();
+        includes.read_line(&mut line)?;
+        let (path, number) = includes.location()?;
+        let location = format!("{}:{}", path.display(), number);
+
+        // comments and blank lines are skipped
+        if command::strip_comment(&line).trim().is_empty() {
+            continue;
+        }
+
+        let command = command::strip_prefix(command::strip_comment(&line), command::PREFIX, false);
+        match command.and_then(include::parse) {
+            Some(path) => {
+                if let Err(message) = includes.open(path) {
+                    problems.push((location, "error", message));
+                }
+            }
+            None => return Some((location, line)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // check a script (main.txt) with the given files next to it, returning
+    // the problems with the locations relative to the directory
+    fn problems(test: &str, script: &str, files: &[(&str, &str)]) -> Vec<(String, String)> {
+        let dir = std::env::temp_dir().join(format!("stdg-check-{}", test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.txt"), script).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        check(dir.join("main.txt").to_str().unwrap())
+            .into_iter()
+            .map(|(location, severity, message)| {
+                let location = location.strip_prefix(&prefix).unwrap_or(&location);
+                (format!("{} {}", location, severity), message)
+            })
+            .collect()
+    }
+
+    fn problem(location: &str, message: &str) -> (String, String) {
+        (location.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn fine() {
+        let script = "start 100 100 Fine version=0.1\n\
+                      push\n\
+                      translate 10 10\n\
+                      # pop\n\
+                      pop\n\
+                      open a.png as a\n\
+                      image a 0 0\n";
+        assert_eq!(problems("fine", script, &[]), []);
+    }
+
+    #[test]
+    fn start_line() {
+        assert_eq!(
+            problems("start", "rect 0 0 1 1\n", &[]),
+            [problem("main.txt:1 error", "expected start command")]
+        );
+        assert_eq!(
+            problems("start-width", "@stdg start wide 100 A\n", &[]),
+            [problem(
+                "main.txt:1 error",
+                "expected integer (usize) width of window"
+            )]
+        );
+        assert_eq!(
+            problems("start-version", "start 1 1 A version=99\n", &[]),
+            [problem(
+                "main.txt:1 error",
+                &format!(
+                    "expected stdg 99 or newer but this is stdg {}",
+                    command::VERSION
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn arity() {
+        let script = "start 100 100 A\nrect 1 2 3\nstart 100 100 A\n";
+        assert_eq!(
+            problems("arity", script, &[]),
+            [
+                problem(
+                    "main.txt:2 error",
+                    "expected `rect x y width height` but got 3 arguments, line will be printed"
+                ),
+                problem(
+                    "main.txt:3 error",
+                    "start must only be the first line, line will be printed"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let script = "start 100 100 A\npop\npush\npush\npop\n";
+        assert_eq!(
+            problems("push", script, &[]),
+            [
+                problem("main.txt:2 error", "pop without push"),
+                problem("main.txt:3 error", "push without pop"),
+            ]
+        );
+    }
+
+    #[test]
+    fn images() {
+        let script = "start 100 100 A\nimage a 0 0\nopen a.png as a\nimage a 0 0\n";
+        assert_eq!(
+            problems("images", script, &[]),
+            [problem(
+                "main.txt:2 error",
+                "image `a` is drawn before it is opened"
+            )]
+        );
+    }
+
+    #[test]
+    fn text() {
+        let script = "start 100 100 A\ntext 0 0\nhello\ntextsize 10\ntext 0 0\nhi\ntextfont a.ttf\ntext 0 0\nrect\n";
+        assert_eq!(
+            problems("text", script, &[]),
+            [
+                problem("main.txt:2 error", "text before textfont"),
+                problem("main.txt:2 error", "text before textsize"),
+                problem("main.txt:5 error", "text before textfont"),
+            ]
+        );
+        assert_eq!(
+            problems(
+                "text-end",
+                "start 100 100 A\ntextfont a.ttf\ntextsize 1\ntext 0 0\n",
+                &[]
+            ),
+            [problem(
+                "main.txt:4 error",
+                "text must be followed by line with text"
+            )]
+        );
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let script = "start 100 100 A\nhello there\ndefine dot x\ncircle $x 0 1\nend\ndot 1\ndot\n";
+        assert_eq!(
+            problems("unknown", script, &[]),
+            [
+                problem(
+                    "main.txt:2 warning",
+                    "`hello` is not a command, line will be printed"
+                ),
+                problem("main.txt:7 error", "expected 1 arguments to dot but got 0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn include_locations() {
+        let script = "start 100 100 A\ninclude a.txt\npop\ninclude missing.txt\n";
+        let files = [
+            ("a.txt", "nofill\n@stdg include b.txt\nrect\n"),
+            ("b.txt", "\nbogus line\n"),
+        ];
+        assert_eq!(
+            problems("include", script, &files),
+            [
+                problem(
+                    "b.txt:2 warning",
+                    "`bogus` is not a command, line will be printed"
+                ),
+                problem(
+                    "a.txt:3 error",
+                    "expected `rect x y width height` but got 0 arguments, line will be printed"
+                ),
+                problem("main.txt:3 error", "pop without push"),
+                (
+                    "main.txt:4 error".to_string(),
+                    format!(
+                        "failed to include {}, file not found",
+                        std::env::temp_dir()
+                            .join("stdg-check-include")
+                            .join("missing.txt")
+                            .display()
+                    )
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,674 @@
+// for launching and talking to the client
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::binary::{self, Frame};
+use crate::command;
+use crate::include::{self, Includes};
+use crate::json;
+use crate::repl::Lines;
+use crate::session::{Recorder, Replay};
+
+// where commands come from
+enum Source {
+    // a process we launched, we read its stdout and can reply to its stdin
+    //
+    // the process is shared so that it can be killed while we are waiting
+    // for it to print something
+    Process {
+        child: Arc<Mutex<Child>>,
+        input: ChildStdin,
+        output: BufReader<ChildStdout>,
+    },
+    // something piped into stdg or a file of commands, we can only read from
+    // it
+    Reader(Box<dyn BufRead + Send>),
+    // a connection to a socket we listened on, which we can also reply to
+    Stream {
+        input: Box<dyn Write + Send>,
+        output: Box<dyn BufRead + Send>,
+    },
+    // a recorded session, replies come from the log
+    Replay(Replay),
+    // lines typed into the repl, replies and errors are printed
+    Repl(Lines),
+}
+
+impl Source {
+    fn read_line(&mut self, buf: &mut String) -> usize {
+        match self {
+            Source::Process { output, .. } => output
+                .read_line(buf)
+                .expect("expected command to draw graphics"),
+            Source::Reader(reader) | Source::Stream { output: reader, .. } => reader
+                .read_line(buf)
+                .expect("expected command to draw graphics"),
+            Source::Replay(replay) => match replay.next_input() {
+                Some(line) => {
+                    buf.push_str(&line);
+                    buf.push('\n');
+                    line.len() + 1
+                }
+                None => 0,
+            },
+            Source::Repl(lines) => lines.read_line(buf),
+        }
+    }
+
+    fn read_frame(&mut self, buf: &mut String, numbers: &mut Vec<f32>) -> Frame {
+        match self {
+            Source::Process { output, .. } => binary::read(output, buf, numbers),
+            Source::Reader(reader) | Source::Stream { output: reader, .. } => {
+                binary::read(reader, buf, numbers)
+            }
+            // sessions are recorded as lines, even with binary framing, and
+            // the repl has no start line to ask for binary framing with
+            Source::Replay(_) | Source::Repl(_) => unreachable!(),
+        }
+        .expect("expected frame with command to draw graphics")
+    }
+}
+
+// the client is whatever is printing commands for stdg to handle
+pub struct Client {
+    source: Source,
+    // files spliced in with include commands
+    includes: Includes,
+    // whether to skip comments and blank lines
+    strip_comments: bool,
+    // what lines that are commands start with and whether they must
+    prefix: String,
+    prefix_required: bool,
+    // whether everything after the start line is binary frames and, if the
+    // last frame read was a command sent as numbers, its opcode and numbers
+    binary: bool,
+    framed: Option<usize>,
+    numbers: Vec<f32>,
+    // whether lines can be JSON and the lines left from the last line of
+    // JSON read
+    json: bool,
+    from_json: VecDeque<String>,
+    // whether commands are read from a file and whether the line just read
+    // is from a file (or from JSON), where every line is meant as a command
+    file: bool,
+    from_file: bool,
+    recorder: Option<Recorder>,
+    // whether to keep presenting what was drawn last once there is nothing
+    // more to read and whether there is nothing more to read
+    keep_open: bool,
+    ended: bool,
+    // how long a process has to exit once asked to close and, if it was
+    // asked, whether it still has to
+    grace_period: Duration,
+    closing: Option<Arc<Closing>>,
+}
+
+// a process asked to close either cancels closing or is killed once its
+// grace period is over
+#[derive(Default)]
+struct Closing {
+    cancelled: AtomicBool,
+    killed: AtomicBool,
+}
+
+// how long a process has to close by default
+pub const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+// how to launch a process, from the arguments after stdg's options and
+// --cwd, --env and --shell
+#[derive(Clone, Default)]
+pub struct Launch {
+    pub command: Vec<String>,
+    // the directory to run the process in and variables to add to its
+    // environment
+    pub cwd: Option<String>,
+    pub env: Vec<(String, String)>,
+    // run the arguments as one line with the shell, so that things like &&
+    // and pipes work
+    pub shell: bool,
+}
+
+impl Launch {
+    fn command(&self) -> Command {
+        let mut command = if self.shell {
+            let line = self.command.join(" ");
+            if cfg!(windows) {
+                let mut command = Command::new("cmd");
+                command.arg("/C").arg(line);
+                command
+            } else {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(line);
+                command
+            }
+        } else {
+            let mut command = Command::new(&self.command[0]);
+            command.args(&self.command[1..]);
+            command
+        };
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command.envs(self.env.iter().cloned());
+        command
+    }
+}
+
+impl Client {
+    // launch the given process with its stdin and stdout piped to us
+    pub fn spawn(launch: &Launch) -> Client {
+        let mut child = launch
+            .command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // anything the process prints to stderr goes straight to ours
+            .stderr(Stdio::inherit())
+            .spawn()
+            .expect("failed to execute process");
+        let input = child.stdin.take().expect("failed to get stdin to process");
+        let output = BufReader::new(
+            child
+                .stdout
+                .take()
+                .expect("failed to get stdout from process"),
+        );
+
+        Client::new(
+            Source::Process {
+                child: Arc::new(Mutex::new(child)),
+                input,
+                output,
+            },
+            None,
+        )
+    }
+
+    pub fn stdin() -> Client {
+        Client::new(
+            Source::Reader(Box::new(BufReader::new(std::io::stdin()))),
+            None,
+        )
+    }
+
+    // read commands from a file, like a text file piped into stdg
+    pub fn file(path: &str) -> Client {
+        Client::new(
+            Source::Reader(Box::new(BufReader::new(
+                File::open(path).expect("failed to open file"),
+            ))),
+            Some(path),
+        )
+    }
+
+    // a connection to a socket we are listening on
+    pub fn stream(input: Box<dyn Write + Send>, output: Box<dyn BufRead + Send>) -> Client {
+        Client::new(Source::Stream { input, output }, None)
+    }
+
+    // included files were already spliced in when the session was recorded
+    pub fn replay(replay: Replay) -> Client {
+        Client::new(Source::Replay(replay), None)
+    }
+
+    // lines typed into the repl
+    pub fn repl(lines: Lines) -> Client {
+        Client::new(Source::Repl(lines), None)
+    }
+
+    // paths in include commands are relative to the file the commands are
+    // from, if there is one
+    fn new(source: Source, file: Option<&str>) -> Client {
+        Client {
+            source,
+            includes: Includes::new(file),
+            strip_comments: file.is_some(),
+            prefix: command::PREFIX.to_string(),
+            prefix_required: false,
+            binary: false,
+            framed: None,
+            numbers: vec![],
+            json: false,
+            from_json: VecDeque::new(),
+            file: file.is_some(),
+            from_file: false,
+            recorder: None,
+            keep_open: false,
+            ended: false,
+            grace_period: GRACE_PERIOD,
+            closing: None,
+        }
+    }
+
+    // skip comments and blank lines, which are always skipped in files but
+    // otherwise are printed like any other line that isn't a command
+    pub fn strip_comments(&mut self) {
+        self.strip_comments = true;
+    }
+
+    // only treat lines starting with the given prefix as commands
+    pub fn require_prefix(&mut self, prefix: String) {
+        self.prefix = prefix;
+        self.prefix_required = true;
+    }
+
+    // the part of a line that is a command, without any prefix
+    //
+    // returns None if the line can't be a command, lines from files (and
+    // JSON) are always commands so they don't need the prefix
+    pub fn strip_prefix<'a>(&self, line: &'a str) -> Option<&'a str> {
+        command::strip_prefix(line, &self.prefix, self.prefix_required && !self.from_file)
+    }
+
+    // whether a line has to be a command, because it is from a file (or
+    // JSON), was typed into the repl or starts with the prefix, rather than
+    // being output that only looks like one
+    pub fn is_meant_as_command(&self, line: &str) -> bool {
+        self.from_file
+            || self.is_interactive()
+            || command::strip_prefix(line, &self.prefix, true).is_some()
+    }
+
+    // read binary frames from now on
+    //
+    // a recorded session is replayed as lines, since that's how it was
+    // recorded
+    pub fn binary(&mut self) {
+        self.binary = !self.is_replay();
+    }
+
+    // the opcode and numbers of the command that was just read, if it was
+    // sent as numbers with binary framing
+    pub fn frame(&mut self) -> Option<(usize, &[f32])> {
+        let opcode = self.framed.take()?;
+        Some((opcode, &self.numbers))
+    }
+
+    // read lines of JSON as the commands they are, and reply and send back
+    // errors as JSON
+    pub fn json(&mut self) {
+        self.json = true;
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    // whether lines are typed in as we go (into the repl), rather than printed
+    // by a client that knows what it's doing
+    pub fn is_interactive(&self) -> bool {
+        matches!(self.source, Source::Repl(_))
+    }
+
+    // whether something wrong with a command is reported and we keep going,
+    // rather than stopping
+    pub fn keeps_going(&self) -> bool {
+        self.json || self.is_interactive()
+    }
+
+    // whether a line can be read without waiting for it, which is only ever
+    // not the case for lines typed into the repl
+    //
+    // what is being read is whether the line is text to draw and whether a
+    // block is being collected
+    pub fn is_ready(&mut self, text: bool, collecting: bool) -> bool {
+        match &mut self.source {
+            Source::Repl(lines) if self.from_json.is_empty() && !self.includes.is_reading() => {
+                lines.is_ready(text, collecting)
+            }
+            _ => true,
+        }
+    }
+
+    // something is wrong with a command
+    //
+    // with JSON, the error is sent back (or printed if there's nothing to
+    // send it back to) and we keep going, in the repl it's printed and we
+    // keep going, otherwise we stop
+    pub fn error(&mut self, message: &str) {
+        if self.is_interactive() {
+            println!("error: {}", message);
+            return;
+        }
+        if !self.json {
+            panic!("{}", message);
+        }
+        let error = json::error(message);
+        match self.source {
+            Source::Reader(_) => println!("{}", error),
+            _ => self.reply(|| error),
+        }
+    }
+
+    // record everything read from and replied to this client
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    // keep the window open once the client is done
+    pub fn keep_open(&mut self) {
+        self.keep_open = true;
+    }
+
+    pub fn keeps_open(&self) -> bool {
+        self.keep_open
+    }
+
+    pub fn grace_period(&mut self, grace_period: Duration) {
+        self.grace_period = grace_period;
+    }
+
+    // every file that has been included so far
+    pub fn included(&self) -> &[PathBuf] {
+        self.includes.included()
+    }
+
+    pub fn is_replay(&self) -> bool {
+        matches!(self.source, Source::Replay(_))
+    }
+
+    // read a line into the given string, returns the number of bytes read
+    pub fn read_line(&mut self, buf: &mut String) -> usize {
+        self.read(buf, false)
+    }
+
+    // read a line of text to draw, which is never a command or a comment
+    pub fn read_text(&mut self, buf: &mut String) -> usize {
+        self.read(buf, true)
+    }
+
+    fn read(&mut self, buf: &mut String, text: bool) -> usize {
+        let start = buf.len();
+        loop {
+            self.framed = None;
+            if let Some(line) = self.from_json.pop_front() {
+                self.from_file = true;
+                if let Some(path) = include::parse(&line).filter(|_| !text) {
+                    if let Err(message) = self.includes.open(path) {
+                        self.error(&message);
+                    }
+                    continue;
+                }
+                buf.push_str(&line);
+                buf.push('\n');
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.input(&line);
+                }
+                return line.len() + 1;
+            }
+            let (read, included) = match self.includes.read_line(buf) {
+                Some(read) => (read, true),
+                None if self.binary => match self.source.read_frame(buf, &mut self.numbers) {
+                    Frame::Line(read) => (read, false),
+                    Frame::Numbers(_) if text => panic!("expected line with text to draw"),
+                    Frame::Numbers(opcode) => {
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.input(&binary::line(opcode, &self.numbers));
+                        }
+                        self.framed = Some(opcode);
+                        return 3 + 4 * self.numbers.len();
+                    }
+                    Frame::End => (0, false),
+                },
+                None => (self.source.read_line(buf), false),
+            };
+            self.from_file = included || self.file;
+
+            // comments and blank lines are skipped in files (and in anything
+            // else if we were asked to strip comments)
+            if read > 0 && !text && (included || self.strip_comments) {
+                let line = command::strip_comment(&buf[start..]).trim_end().to_string();
+                buf.truncate(start);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                buf.push_str(&line);
+                buf.push('\n');
+            }
+
+            // a line of JSON is replaced by the lines of the commands in it
+            let lines = match self.json && !text {
+                true => json::lines(&buf[start..]),
+                false => None,
+            };
+            if let Some(lines) = lines {
+                buf.truncate(start);
+                match lines {
+                    Ok(lines) => self.from_json.extend(lines),
+                    Err(message) => self.error(&message),
+                }
+                continue;
+            }
+
+            // an include command is replaced by the lines of the file
+            //
+            // a line that only looks like one (like include the header below
+            // printed by a process) and can't be included is printed like
+            // any other line that isn't a command
+            let path = self
+                .strip_prefix(&buf[start..])
+                .and_then(include::parse)
+                .filter(|_| !text);
+            match path
+                .map(str::to_string)
+                .map(|path| self.includes.open(&path))
+            {
+                Some(Ok(())) => {
+                    buf.truncate(start);
+                    continue;
+                }
+                Some(Err(message)) if self.is_meant_as_command(&buf[start..]) => {
+                    buf.truncate(start);
+                    self.error(&message);
+                    continue;
+                }
+                _ => {}
+            }
+
+            if read > 0 {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.input(&buf[start..]);
+                }
+            } else {
+                self.ended = true;
+            }
+            return read;
+        }
+    }
+
+    // send the answer to a get command
+    //
+    // when replaying, the answer is whatever was recorded and the given
+    // function isn't called
+    pub fn reply(&mut self, answer: impl FnOnce() -> String) {
+        let reply = match &mut self.source {
+            Source::Process { input, .. } => {
+                let reply = answer();
+                writeln!(input, "{}", reply).expect("failed to print to process");
+                reply
+            }
+            Source::Stream { input, .. } => {
+                let reply = answer();
+                writeln!(input, "{}", reply).expect("failed to print to connection");
+                reply
+            }
+            Source::Reader(_) => {
+                panic!("process must be passed as argument to stdg for answers to get commands to be printed to it")
+            }
+            Source::Replay(replay) => replay.next_reply(),
+            Source::Repl(_) => {
+                let reply = answer();
+                println!("{}", reply);
+                reply
+            }
+        };
+        if let Some(recorder) = &mut self.recorder {
+            recorder.reply(&reply);
+        }
+    }
+
+    // send something to the client without it asking, like a close event,
+    // returns false if it couldn't be sent
+    //
+    // a client that is done printing commands may have exited already, so
+    // failing to send it something isn't a problem
+    pub fn event(&mut self, event: &str) -> bool {
+        let event = if self.json {
+            json::event(event)
+        } else {
+            event.to_string()
+        };
+        let sent = match &mut self.source {
+            Source::Process { input, .. } => writeln!(input, "{}", event),
+            Source::Stream { input, .. } => writeln!(input, "{}", event),
+            _ => return false,
+        };
+        if sent.is_err() {
+            return false;
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.event(&event);
+        }
+        true
+    }
+
+    // ask the process we launched to close, because its window was closed
+    //
+    // it is sent a close event and has the grace period to exit (or print
+    // close cancel) before it is killed, returns false if there is no process
+    // to ask
+    pub fn ask_to_close(&mut self) -> bool {
+        let child = match &self.source {
+            Source::Process { child, .. } => child.clone(),
+            _ => return false,
+        };
+        // without a grace period, the process is killed right away, and one
+        // that can't be sent the close event has exited already
+        if self.grace_period.is_zero() || !self.event("close") {
+            return false;
+        }
+
+        let closing = Arc::new(Closing::default());
+        let grace_period = self.grace_period;
+        self.closing = Some(closing.clone());
+        thread::spawn(move || {
+            thread::sleep(grace_period);
+            if !closing.cancelled.load(Ordering::SeqCst) {
+                closing.killed.store(true, Ordering::SeqCst);
+                let _ = child.lock().unwrap().kill();
+            }
+        });
+        true
+    }
+
+    // the process we launched, so that it can be killed from elsewhere
+    pub fn process(&self) -> Option<Arc<Mutex<Child>>> {
+        match &self.source {
+            Source::Process { child, .. } => Some(child.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.closing.is_some()
+    }
+
+    // the process printed close cancel, returns false if it wasn't asked to
+    // close
+    pub fn cancel_close(&mut self) -> bool {
+        match self.closing.take() {
+            Some(closing) => {
+                closing.cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // close the process we launched, if any, and get its exit code
+    //
+    // a process that is done printing commands is waited for, but one that
+    // is still going (because the window was closed) is killed and that
+    // counts as exiting fine
+    pub fn close(self) -> i32 {
+        let (child, input) = match self.source {
+            Source::Process { child, input, .. } => (child, input),
+            _ => return 0,
+        };
+        // a process that reads until its input ends wouldn't exit while we
+        // wait for it otherwise
+        drop(input);
+        if !self.ended {
+            child
+                .lock()
+                .unwrap()
+                .kill()
+                .expect("failed to kill process");
+            return 0;
+        }
+        // the process is checked on instead of waited for, so that it can
+        // still be killed (once the grace period is up or something changed
+        // with --watch) while we wait
+        let status = loop {
+            let status = child.lock().unwrap().try_wait();
+            match status.expect("failed to wait for process") {
+                Some(status) => break status,
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        let killed = self
+            .closing
+            .is_some_and(|closing| closing.killed.load(Ordering::SeqCst));
+        match status.code() {
+            Some(code) => code,
+            _ if killed => 0,
+            // killed by a signal, which shells report as 128 plus the signal
+            None => exit_signal(&status).map_or(1, |signal| 128 + signal),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[cfg(unix)]
+    #[test]
+    fn killed_while_closing() {
+        // a process that is done printing but keeps running can still be
+        // killed from elsewhere while we wait for it to exit
+        let mut client = Client::spawn(&Launch {
+            command: vec!["exec >&-; sleep 30".to_string()],
+            shell: true,
+            ..Launch::default()
+        });
+        assert_eq!(client.read_line(&mut String::new()), 0);
+        let process = client.process().unwrap();
+        let killer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            process.lock().unwrap().kill().unwrap();
+        });
+
+        let started = Instant::now();
+        assert_eq!(client.close(), 128 + 9);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        killer.join().unwrap();
+    }
+}
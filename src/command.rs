@@ -0,0 +1,758 @@
+// for the arguments of commands
+use minifb::{Key, MouseButton};
+use raqote::{LineCap, LineJoin};
+// for etc.
+use std::str::FromStr;
+
+use crate::input;
+
+// every command stdg understands and how it is used
+//
+// this is the grammar of stdg, a line is a command if its first word is
+// listed here and it has one of the listed numbers of words
+//
+// where a command is in this list is its opcode with binary framing, so new
+// commands go at the end
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("start", "start width height title"),
+    ("present", "present [forever]"),
+    ("get", "get query [argument]"),
+    ("nofill", "nofill"),
+    ("nostroke", "nostroke"),
+    ("push", "push"),
+    ("pop", "pop"),
+    ("save", "save file"),
+    ("strokeweight", "strokeweight weight"),
+    ("strokecap", "strokecap square|project|round"),
+    ("strokejoin", "strokejoin miter|bevel|round"),
+    ("rotate", "rotate degrees"),
+    ("textfont", "textfont file"),
+    ("textsize", "textsize size"),
+    ("translate", "translate x y"),
+    ("scale", "scale x y"),
+    ("text", "text x y"),
+    ("open", "open file as name"),
+    ("image", "image name x y [width height]"),
+    ("background", "background red green blue"),
+    ("fill", "fill red green blue [alpha]"),
+    ("stroke", "stroke red green blue [alpha]"),
+    ("circle", "circle x y radius"),
+    ("ellipse", "ellipse x y width height"),
+    ("line", "line x1 y1 x2 y2"),
+    ("rect", "rect x y width height"),
+    ("arc", "arc x y radius start end"),
+    ("poly", "poly points..."),
+    ("print", "print text"),
+    ("set", "set name value"),
+    ("repeat", "repeat count"),
+    ("for", "for name from to [step]"),
+    ("define", "define name [arguments...]"),
+    ("end", "end"),
+    ("layer", "layer name [z]"),
+    ("window", "window name width height title"),
+    ("select", "select name"),
+    ("close", "close cancel"),
+    ("events", "events on|off"),
+    ("include", "include file"),
+];
+
+// commands named with words that lines of ordinary output often start with,
+// like for i in range or close it, so a line starting with one of them that
+// isn't a valid command is printed (unless it is meant as a command)
+pub const WORDS: &[&str] = &[
+    "set", "repeat", "for", "define", "end", "layer", "window", "select", "close", "events",
+    "include",
+];
+
+// what can be asked for with get
+pub const QUERIES: &[&str] = &[
+    "mousex",
+    "mousey",
+    "keys",
+    "mouseispressed",
+    "keyispressed",
+    "version",
+    "capabilities",
+    "keyspressed",
+    "keysreleased",
+    "textinput",
+    "mousewheel",
+    "mouseclicked",
+    "mousedoubleclicked",
+];
+
+// the formats commands can be in, text is the default, binary is chosen with
+// format=binary on the start line and json with --json
+pub const FORMATS: &[&str] = &["text", "binary", "json"];
+
+// the version of stdg, which scripts can ask for with get version or require
+// with version=0.2 on the start line
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// lines starting with this are always commands, so that a line that starts
+// like a command can be printed with @stdg print ...
+//
+// with --prefix, only lines starting with the prefix are commands
+pub const PREFIX: &str = "@stdg";
+
+// a command after it has been parsed from a line
+pub enum Command {
+    Present,
+    PresentForever,
+    Get(Query),
+    NoFill,
+    NoStroke,
+    Push,
+    Pop,
+    Save(String),
+    StrokeWeight(f32),
+    StrokeCap(LineCap),
+    StrokeJoin(LineJoin),
+    Rotate(f32),
+    TextFont(String),
+    TextSize(f32),
+    Translate(f32, f32),
+    Scale(f32, f32),
+    // the text to draw is on the next line
+    Text(f32, f32),
+    Open(String, String),
+    Image(String, f32, f32, Option<(f32, f32)>),
+    Background(u8, u8, u8),
+    Fill(u8, u8, u8, u8),
+    Stroke(u8, u8, u8, u8),
+    Circle(f32, f32, f32),
+    Ellipse(f32, f32, f32, f32),
+    Line(f32, f32, f32, f32),
+    Rect(f32, f32, f32, f32),
+    Arc(f32, f32, f32, f32, f32),
+    Poly(Vec<(f32, f32)>),
+    // print the rest of the line, even if it looks like a command
+    Print(String),
+    // set a variable that can be used in later commands as $name
+    Set(String, f32),
+    // the lines up to the matching end are a block that is run once it ends
+    Repeat(usize),
+    For(String, f32, f32, Option<f32>),
+    Define(String, Vec<String>),
+    End,
+    // with --listen, name the layer the client draws on and set where it is
+    // drawn from bottom to top
+    Layer(String, Option<i32>),
+    // open another window with its own canvas and draw on it from now on
+    Window(String, usize, usize, String),
+    // draw on and get input from another window from now on
+    Select(String),
+    // keep going after being sent a close event because the window was
+    // closed, which opens the window again
+    CloseCancel,
+    // send what happens (like keys going down) to the client each time it
+    // presents, or stop sending it
+    Events(bool),
+}
+
+// what a get command asks for
+#[derive(Clone)]
+pub enum Query {
+    MouseX,
+    MouseY,
+    Keys,
+    MouseIsPressed(MouseButton),
+    KeyIsPressed(Key),
+    Version,
+    Capabilities,
+    // keys that went down or up since the client last presented
+    KeysPressed,
+    KeysReleased,
+    // what was typed since the client last presented
+    TextInput,
+    // how far the mouse wheel was scrolled since this was last asked
+    MouseWheel,
+    // whether a mouse button went down (or went down twice quickly) since
+    // the client last presented
+    MouseClicked(MouseButton),
+    MouseDoubleClicked(MouseButton),
+}
+
+// why a line isn't a command
+pub enum Error {
+    // the first word isn't a command, so the line is just printed
+    Unknown,
+    // the first word is a command but it doesn't have the right number of
+    // words, so the line is also just printed
+    Arity(&'static str),
+    // the line is a command but something about it is wrong
+    Invalid(String),
+}
+
+impl Command {
+    // parse a line split into words
+    pub fn parse(command: &[String]) -> Result<Command, Error> {
+        let name = match command.first() {
+            Some(name) => name.as_str(),
+            None => return Err(Error::Unknown),
+        };
+        let usage = match COMMANDS.iter().find(|(command, _)| *command == name) {
+            Some((_, usage)) => *usage,
+            None => return Err(Error::Unknown),
+        };
+
+        Ok(match (name, command.len()) {
+            ("poly", len) if len >= 3 && len % 2 == 1 => Command::Poly(
+                command[1..]
+                    .chunks(2)
+                    .map(|point| {
+                        Ok((
+                            number(&point[0], "expected x position (f32) of vertex of polygon")?,
+                            number(&point[1], "expected y position (f32) of vertex of polygon")?,
+                        ))
+                    })
+                    .collect::<Result<Vec<(f32, f32)>, Error>>()?,
+            ),
+            ("print", _) => Command::Print(command[1..].join(" ")),
+            ("set", 3) => Command::Set(
+                identifier(&command[1])?,
+                number(&command[2], "expected value (f32) of variable")?,
+            ),
+            ("repeat", 2) => Command::Repeat(number(
+                &command[1],
+                "expected number of times (usize) to repeat",
+            )?),
+            ("for", 4) | ("for", 5) => Command::For(
+                identifier(&command[1])?,
+                number(&command[2], "expected value (f32) to count from")?,
+                number(&command[3], "expected value (f32) to count up to")?,
+                match command.get(4) {
+                    Some(step) => Some(number(step, "expected step (f32) to count by")?),
+                    None => None,
+                },
+            ),
+            ("define", len) if len >= 2 => {
+                if is_command(&command[1]) {
+                    return invalid(&format!(
+                        "can't define {} since it is already a command",
+                        command[1]
+                    ));
+                }
+                Command::Define(
+                    identifier(&command[1])?,
+                    command[2..]
+                        .iter()
+                        .map(|argument| identifier(argument))
+                        .collect::<Result<Vec<String>, Error>>()?,
+                )
+            }
+            ("end", 1) => Command::End,
+            ("layer", 2) | ("layer", 3) => Command::Layer(
+                identifier(&command[1])?,
+                match command.get(2) {
+                    Some(z) => Some(number(z, "expected z (i32) of layer")?),
+                    None => None,
+                },
+            ),
+            ("window", len) if len >= 5 => Command::Window(
+                identifier(&command[1])?,
+                number(&command[2], "expected width (usize) of window")?,
+                number(&command[3], "expected height (usize) of window")?,
+                command[4..].join(" "),
+            ),
+            ("select", 2) => Command::Select(identifier(&command[1])?),
+            ("events", 2) => match command[1].as_str() {
+                "on" => Command::Events(true),
+                "off" => Command::Events(false),
+                _ => return invalid("expected events on or events off"),
+            },
+            ("close", 2) => match command[1].as_str() {
+                "cancel" => Command::CloseCancel,
+                _ => return invalid("unsupported usage of close command"),
+            },
+            ("present", 1) => Command::Present,
+            ("present", 2) => match command[1].as_str() {
+                "forever" => Command::PresentForever,
+                _ => return invalid("unsupported usage of present command"),
+            },
+            ("get", 2) | ("get", 3) => Command::Get(Query::parse(&command[1..])?),
+            ("nofill", 1) => Command::NoFill,
+            ("nostroke", 1) => Command::NoStroke,
+            ("push", 1) => Command::Push,
+            ("pop", 1) => Command::Pop,
+            ("save", 2) => Command::Save(command[1].clone()),
+            ("strokeweight", 2) => {
+                Command::StrokeWeight(number(&command[1], "expected stroke weight (f32)")?)
+            }
+            ("strokecap", 2) => Command::StrokeCap(match command[1].as_str() {
+                "square" => LineCap::Square,
+                "project" => LineCap::Butt,
+                "round" => LineCap::Round,
+                _ => return invalid("expected either square, project, or round for stroke cap"),
+            }),
+            ("strokejoin", 2) => Command::StrokeJoin(match command[1].as_str() {
+                "miter" => LineJoin::Miter,
+                "bevel" => LineJoin::Bevel,
+                "round" => LineJoin::Round,
+                _ => return invalid("expected either miter, bevel, or round for stroke join"),
+            }),
+            ("rotate", 2) => Command::Rotate(number(
+                &command[1],
+                "expected rotation value in degrees (f32) for rotation",
+            )?),
+            ("textfont", 2) => Command::TextFont(command[1].clone()),
+            ("textsize", 2) => {
+                Command::TextSize(number(&command[1], "expected size of text (f32)")?)
+            }
+            ("translate", 3) => Command::Translate(
+                number(&command[1], "expected x value (f32) for translation")?,
+                number(&command[2], "expected y value (f32) for translation")?,
+            ),
+            ("scale", 3) => Command::Scale(
+                number(&command[1], "expected x value (f32) for scaling")?,
+                number(&command[2], "expected y value (f32) for scaling")?,
+            ),
+            ("text", 3) => Command::Text(
+                number(&command[1], "expected x position of text (f32)")?,
+                number(&command[2], "expected y position of text (f32)")?,
+            ),
+            ("open", 4) => {
+                if command[2] != "as" {
+                    return invalid("expected open file as name");
+                }
+                Command::Open(command[1].clone(), command[3].clone())
+            }
+            ("image", 4) | ("image", 6) => Command::Image(
+                command[1].clone(),
+                number(&command[2], "expected x position (f32) of image")?,
+                number(&command[3], "expected y position (f32) of image")?,
+                match command.get(4..6) {
+                    Some(size) => Some((
+                        number(&size[0], "expected width (f32) of image")?,
+                        number(&size[1], "expected height (f32) of image")?,
+                    )),
+                    None => None,
+                },
+            ),
+            ("background", 4) => Command::Background(
+                number(&command[1], "expected red value (u8) of color")?,
+                number(&command[2], "expected green value (u8) of color")?,
+                number(&command[3], "expected blue value (u8) of color")?,
+            ),
+            ("fill", 4) | ("fill", 5) => {
+                let (r, g, b, a) = color(command)?;
+                Command::Fill(r, g, b, a)
+            }
+            ("stroke", 4) | ("stroke", 5) => {
+                let (r, g, b, a) = color(command)?;
+                Command::Stroke(r, g, b, a)
+            }
+            ("circle", 4) => Command::Circle(
+                number(&command[1], "expected x position (f32) of circle")?,
+                number(&command[2], "expected y position (f32) of circle")?,
+                number(&command[3], "expected radius (f32) of circle")?,
+            ),
+            ("ellipse", 5) => Command::Ellipse(
+                number(&command[1], "expected x position (f32) of ellipse")?,
+                number(&command[2], "expected y position (f32) of ellipse")?,
+                number(&command[3], "expected width (f32) of ellipse")?,
+                number(&command[4], "expected height (f32) of ellipse")?,
+            ),
+            ("line", 5) => Command::Line(
+                number(&command[1], "expected x position (f32) of start of line")?,
+                number(&command[2], "expected y position (f32) of start of line")?,
+                number(&command[3], "expected x position (f32) of end of line")?,
+                number(&command[4], "expected y position (f32) of end of line")?,
+            ),
+            ("rect", 5) => Command::Rect(
+                number(&command[1], "expected x position (f32) of rectangle")?,
+                number(&command[2], "expected y position (f32) of rectangle")?,
+                number(&command[3], "expected width (f32) of rectangle")?,
+                number(&command[4], "expected height (f32) of rectangle")?,
+            ),
+            ("arc", 6) => Command::Arc(
+                number(&command[1], "expected x position (f32) of arc")?,
+                number(&command[2], "expected y position (f32) of arc")?,
+                number(&command[3], "expected radius (f32) of arc")?,
+                number(&command[4], "expected start angle (f32) of arc")?,
+                number(&command[5], "expected end angle (f32) of arc")?,
+            ),
+            _ => return Err(Error::Arity(usage)),
+        })
+    }
+}
+
+impl Command {
+    // make a command sent as numbers with binary framing, from its opcode
+    // and arguments
+    //
+    // this is the same as parsing the line the numbers would have been on,
+    // but without any words to split or parse
+    pub fn decode(opcode: usize, numbers: &[f32]) -> Result<Command, Error> {
+        let (name, usage) = match COMMANDS.get(opcode) {
+            Some(command) => *command,
+            None => return invalid(&format!("unsupported opcode {}", opcode)),
+        };
+        let n = numbers;
+
+        Ok(match (name, n.len()) {
+            ("poly", len) if len >= 2 && len % 2 == 0 => {
+                Command::Poly(n.chunks(2).map(|point| (point[0], point[1])).collect())
+            }
+            ("present", 0) => Command::Present,
+            ("nofill", 0) => Command::NoFill,
+            ("nostroke", 0) => Command::NoStroke,
+            ("push", 0) => Command::Push,
+            ("pop", 0) => Command::Pop,
+            ("strokeweight", 1) => Command::StrokeWeight(n[0]),
+            ("rotate", 1) => Command::Rotate(n[0]),
+            ("textsize", 1) => Command::TextSize(n[0]),
+            ("translate", 2) => Command::Translate(n[0], n[1]),
+            ("scale", 2) => Command::Scale(n[0], n[1]),
+            ("text", 2) => Command::Text(n[0], n[1]),
+            ("background", 3) => Command::Background(n[0] as u8, n[1] as u8, n[2] as u8),
+            ("fill", 3) | ("fill", 4) => Command::Fill(
+                n[0] as u8,
+                n[1] as u8,
+                n[2] as u8,
+                n.get(3).map_or(255, |alpha| *alpha as u8),
+            ),
+            ("stroke", 3) | ("stroke", 4) => Command::Stroke(
+                n[0] as u8,
+                n[1] as u8,
+                n[2] as u8,
+                n.get(3).map_or(255, |alpha| *alpha as u8),
+            ),
+            ("circle", 3) => Command::Circle(n[0], n[1], n[2]),
+            ("ellipse", 4) => Command::Ellipse(n[0], n[1], n[2], n[3]),
+            ("line", 4) => Command::Line(n[0], n[1], n[2], n[3]),
+            ("rect", 4) => Command::Rect(n[0], n[1], n[2], n[3]),
+            ("arc", 5) => Command::Arc(n[0], n[1], n[2], n[3], n[4]),
+            ("repeat", 1) => Command::Repeat(n[0] as usize),
+            ("end", 0) => Command::End,
+            _ => {
+                return invalid(&format!(
+                    "expected `{}` with only numbers for opcode {}, otherwise send it as a line",
+                    usage, opcode
+                ))
+            }
+        })
+    }
+}
+
+impl Query {
+    // parse the words after get
+    pub fn parse(query: &[String]) -> Result<Query, Error> {
+        Ok(match (query[0].as_str(), query.len()) {
+            ("mousex", 1) => Query::MouseX,
+            ("mousey", 1) => Query::MouseY,
+            ("keys", 1) => Query::Keys,
+            ("version", 1) => Query::Version,
+            ("capabilities", 1) => Query::Capabilities,
+            ("keyspressed", 1) => Query::KeysPressed,
+            ("keysreleased", 1) => Query::KeysReleased,
+            ("textinput", 1) => Query::TextInput,
+            ("mousewheel", 1) => Query::MouseWheel,
+            ("mouseispressed", 2) => Query::MouseIsPressed(button(
+                &query[1],
+                "expected either left, center, or right mouse button ask if pressed",
+            )?),
+            ("mouseclicked", 2) => Query::MouseClicked(button(
+                &query[1],
+                "expected either left, center, or right mouse button ask if clicked",
+            )?),
+            ("mousedoubleclicked", 2) => Query::MouseDoubleClicked(button(
+                &query[1],
+                "expected either left, center, or right mouse button ask if double clicked",
+            )?),
+            ("keyispressed", 2) => match input::key(&query[1]) {
+                Some(key) => Query::KeyIsPressed(key),
+                None => return invalid("unsupported key used"),
+            },
+            _ => return invalid("unsupported usage of get command"),
+        })
+    }
+}
+
+// what this stdg can do, as words like commands=start,present,...
+//
+// clients can look for what they need in this rather than finding out that
+// a command isn't supported by it being printed
+pub fn capabilities() -> String {
+    format!(
+        "version={} commands={} queries={} formats={}",
+        VERSION,
+        COMMANDS
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(","),
+        QUERIES.join(","),
+        FORMATS.join(",")
+    )
+}
+
+// whether this stdg is at least the given version, like 0.2 or 1
+//
+// returns None if it isn't a version
+pub fn supports_version(version: &str) -> Option<bool> {
+    let numbers = |version: &str| {
+        version
+            .split('.')
+            .map(|number| number.parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+    };
+    let mut required = numbers(version).ok()?;
+    let mut current = numbers(VERSION).unwrap();
+    let len = required.len().max(current.len());
+    required.resize(len, 0);
+    current.resize(len, 0);
+    Some(current >= required)
+}
+
+// the start line, which is the first line printed by any client
+pub struct Start {
+    pub width: usize,
+    pub height: usize,
+    pub title: String,
+    // whether the rest of the commands are binary frames
+    pub binary: bool,
+}
+
+impl Start {
+    // parse the start line (after any prefix)
+    pub fn parse(line: &str) -> Result<Start, String> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        if words.first() != Some(&"start") {
+            return Err("expected start command".to_string());
+        }
+        let width = words
+            .get(1)
+            .ok_or("expected width of window")?
+            .parse::<usize>()
+            .map_err(|_| "expected integer (usize) width of window")?;
+        let height = words
+            .get(2)
+            .ok_or("expected height of window")?
+            .parse::<usize>()
+            .map_err(|_| "expected integer (usize) height of window")?;
+        let mut title = words.get(3..).ok_or("expected title of window")?;
+
+        // options go at the end of the start line, like format=binary or
+        // version=0.2
+        let mut binary = false;
+        while let Some((option, rest)) = title.split_last() {
+            match *option {
+                "format=binary" => binary = true,
+                "format=text" => {}
+                option if option.starts_with("version=") => {
+                    let version = &option["version=".len()..];
+                    match supports_version(version) {
+                        Some(true) => {}
+                        Some(false) => {
+                            return Err(format!(
+                                "expected stdg {} or newer but this is stdg {}",
+                                version, VERSION
+                            ))
+                        }
+                        None => {
+                            return Err("expected version like 0.2 or 1 on start line".to_string())
+                        }
+                    }
+                }
+                _ => break,
+            }
+            title = rest;
+        }
+
+        Ok(Start {
+            width,
+            height,
+            title: title.join(" "),
+            binary,
+        })
+    }
+}
+
+// whether the given word is the name of a command
+pub fn is_command(name: &str) -> bool {
+    COMMANDS.iter().any(|(command, _)| *command == name)
+}
+
+// the words in how commands are used that stand for something other than a
+// number, like a file or a name
+const NOT_NUMBERS: &[&str] = &[
+    "title",
+    "file",
+    "as",
+    "name",
+    "query",
+    "argument",
+    "arguments",
+    "text",
+    "forever",
+    "cancel",
+];
+
+// whether the word at the given position of a command (where the name of the
+// command is at 0) is a number, going by how the command is used
+//
+// the last word of a usage goes on for the rest of the line, like the title
+// of a window or the points of poly
+pub fn is_number(name: &str, position: usize) -> bool {
+    let usage = match COMMANDS.iter().find(|(command, _)| *command == name) {
+        Some((_, usage)) => usage,
+        None => return false,
+    };
+    let words: Vec<&str> = usage
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c| c == '[' || c == ']'))
+        .map(|word| word.trim_end_matches("..."))
+        .collect();
+    if position == 0 || words.len() < 2 {
+        return false;
+    }
+    let word = words.get(position).unwrap_or(&words[words.len() - 1]);
+    !word.contains('|') && !NOT_NUMBERS.contains(word)
+}
+
+// the text of a print command exactly as it was printed, with all of its
+// spaces, which splitting it into words would lose
+pub fn print_text(line: &str) -> Option<&str> {
+    let text = line.trim_start().strip_prefix("print")?;
+    let text = text.trim_end_matches(['\n', '\r']);
+    match text.chars().next() {
+        None => Some(text),
+        Some(c) if c.is_whitespace() => Some(&text[c.len_utf8()..]),
+        _ => None,
+    }
+}
+
+// the rest of a line after the given prefix, if the line is a command
+//
+// a line without the prefix is a command only if the prefix isn't required
+pub fn strip_prefix<'a>(line: &'a str, prefix: &str, required: bool) -> Option<&'a str> {
+    match line.trim_start().strip_prefix(prefix) {
+        Some(rest) if rest.starts_with(char::is_whitespace) => Some(rest),
+        _ if !required => Some(line),
+        _ => None,
+    }
+}
+
+// the part of a line before any comment
+//
+// comments start with # or // at the start of the line or after whitespace,
+// so things like save frame#1.png aren't comments
+pub fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        let after_whitespace = previous.is_none_or(char::is_whitespace);
+        if after_whitespace && (c == '#' || line[i..].starts_with("//")) {
+            return &line[..i];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+fn invalid<T>(message: &str) -> Result<T, Error> {
+    Err(Error::Invalid(message.to_string()))
+}
+
+fn number<T: FromStr>(token: &str, message: &str) -> Result<T, Error> {
+    token
+        .parse::<T>()
+        .map_err(|_| Error::Invalid(message.to_string()))
+}
+
+// the mouse button with the given name
+fn button(token: &str, message: &str) -> Result<MouseButton, Error> {
+    input::button(token).ok_or_else(|| Error::Invalid(message.to_string()))
+}
+
+// the name of a variable, argument or defined command
+fn identifier(token: &str) -> Result<String, Error> {
+    if token.starts_with(|c: char| c.is_ascii_digit())
+        || !token.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        return invalid("expected name made of letters, digits and _");
+    }
+    Ok(token.to_string())
+}
+
+// parse the color of a fill or stroke command, alpha is optional
+fn color(command: &[String]) -> Result<(u8, u8, u8, u8), Error> {
+    Ok((
+        number(&command[1], "expected red value (u8) of color")?,
+        number(&command[2], "expected green value (u8) of color")?,
+        number(&command[3], "expected blue value (u8) of color")?,
+        match command.get(4) {
+            Some(alpha) => number(alpha, "expected alpha (transparency, u8) value of color")?,
+            None => 255,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert!(is_number("rect", 1));
+        assert!(is_number("poly", 9));
+        assert!(is_number("window", 3));
+        assert!(is_number("layer", 2));
+        assert!(!is_number("window", 4));
+        assert!(!is_number("window", 6));
+        assert!(!is_number("save", 1));
+        assert!(!is_number("textfont", 1));
+        assert!(!is_number("open", 1));
+        assert!(!is_number("open", 3));
+        assert!(!is_number("print", 1));
+        assert!(!is_number("strokecap", 1));
+        assert!(!is_number("define", 3));
+        assert!(!is_number("nofill", 1));
+        assert!(!is_number("rect", 0));
+    }
+
+    #[test]
+    fn start() {
+        let start = Start::parse("start 400 300 A Rectangle format=binary version=0.1\n").unwrap();
+        assert_eq!(
+            (
+                start.width,
+                start.height,
+                start.title.as_str(),
+                start.binary
+            ),
+            (400, 300, "A Rectangle", true)
+        );
+        // options only go at the end
+        let start = Start::parse("start 1 2 format=binary at the start").unwrap();
+        assert_eq!(
+            (start.title.as_str(), start.binary),
+            ("format=binary at the start", false)
+        );
+        assert_eq!(Start::parse("start 1 2").unwrap().title, "");
+
+        let error = |line| Start::parse(line).err().unwrap();
+        assert_eq!(error("rect 1 2 3 4"), "expected start command");
+        assert_eq!(error(""), "expected start command");
+        assert_eq!(error("start 100"), "expected height of window");
+        assert_eq!(
+            error("start wide 100 A"),
+            "expected integer (usize) width of window"
+        );
+        assert_eq!(
+            error("start 100 100 A version=one"),
+            "expected version like 0.2 or 1 on start line"
+        );
+        assert_eq!(
+            error("start 100 100 A version=999"),
+            format!("expected stdg 999 or newer but this is stdg {}", VERSION)
+        );
+    }
+
+    #[test]
+    fn print() {
+        assert_eq!(print_text("print a  b \n"), Some("a  b "));
+        assert_eq!(print_text("  print\n"), Some(""));
+        assert_eq!(print_text("printer"), None);
+    }
+}
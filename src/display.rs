@@ -0,0 +1,153 @@
+// for displaying window
+use minifb::Window;
+
+use crate::canvas::Canvas;
+use crate::command::Query;
+use crate::input::{self, Input};
+use crate::layer::Layer;
+
+// where what a client draws is presented and where its input comes from
+pub enum Display {
+    // nothing, like when replaying a session or testing a script
+    Headless,
+    // a window, its title (which it is opened again with if closing it is
+    // cancelled) and what happened in it
+    Window {
+        window: Box<Window>,
+        title: String,
+        input: Input,
+    },
+    // a layer of a window shared with other clients
+    Layer(Layer),
+}
+
+impl Display {
+    pub fn window(width: usize, height: usize, title: &str) -> Display {
+        let mut window = Box::new(crate::open_window(width, height, title));
+        let input = Input::default();
+        input.listen(&mut window);
+        Display::Window {
+            window,
+            title: title.to_string(),
+            input,
+        }
+    }
+
+    // a display that doesn't exist is never closed
+    pub fn is_open(&self) -> bool {
+        match self {
+            Display::Headless => true,
+            Display::Window { window, .. } => window.is_open(),
+            Display::Layer(layer) => layer.is_open(),
+        }
+    }
+
+    pub fn is_window(&self) -> bool {
+        matches!(self, Display::Window { .. })
+    }
+
+    // another display like this one, which is a new window if this is a
+    // window
+    pub fn open(&self, width: usize, height: usize, title: &str) -> Result<Display, String> {
+        match self {
+            Display::Headless => Ok(Display::Headless),
+            Display::Window { .. } => Ok(Display::window(width, height, title)),
+            Display::Layer(_) => Err("can't open windows while drawing on a layer".to_string()),
+        }
+    }
+
+    // open a window that was closed again, the same as it was
+    pub fn reopen(&mut self) {
+        if let Display::Window {
+            window,
+            title,
+            input,
+        } = self
+        {
+            let (width, height) = window.get_size();
+            **window = crate::open_window(width, height, title);
+            input.listen(window);
+        }
+    }
+
+    // show everything drawn so far
+    pub fn present(&mut self, canvas: &Canvas) {
+        match self {
+            Display::Headless => {}
+            Display::Window { window, input, .. } => {
+                input.present();
+                window.update_with_buffer(canvas.data()).unwrap();
+                input.track(window);
+            }
+            Display::Layer(layer) => layer.present(canvas),
+        }
+    }
+
+    // keep a window that isn't being presented to responsive and keep track
+    // of what happens in it, without showing anything new
+    pub fn update(&mut self) {
+        if let Display::Window { window, input, .. } = self {
+            window.update();
+            input.track(window);
+        }
+    }
+
+    // show everything drawn so far until the display is closed
+    pub fn present_forever(&mut self, canvas: &Canvas) {
+        match self {
+            Display::Headless => {}
+            Display::Window { window, input, .. } => {
+                while window.is_open() {
+                    window.update_with_buffer(canvas.data()).unwrap();
+                    input.track(window);
+                }
+            }
+            Display::Layer(layer) => {
+                layer.present(canvas);
+                layer.wait_until_closed();
+            }
+        }
+    }
+
+    // answer a get command
+    pub fn query(&mut self, query: Query) -> String {
+        match self {
+            Display::Headless => input::query(None, &query),
+            Display::Window { window, input, .. } => {
+                window.update();
+                input.track(window);
+                input::query(Some((window, input)), &query)
+            }
+            Display::Layer(layer) => layer.query(query),
+        }
+    }
+
+    // send events (or stop sending them) each time the client presents
+    pub fn push(&mut self, push: bool) {
+        match self {
+            Display::Headless => {}
+            Display::Window { input, .. } => input.push(push),
+            Display::Layer(layer) => layer.push(push),
+        }
+    }
+
+    // the events to send to the client
+    pub fn events(&mut self) -> Vec<String> {
+        match self {
+            Display::Headless => vec![],
+            Display::Window { input, .. } => input.events(),
+            Display::Layer(layer) => layer.events(),
+        }
+    }
+
+    // name and order the layer this display is, if it is one
+    pub fn layer(&mut self, name: &str, z: Option<i32>) -> Result<(), String> {
+        match self {
+            Display::Layer(layer) if !layer.claim(name, z) => Err(format!(
+                "layer {} is already drawn on by another client",
+                name
+            )),
+            _ => Ok(()),
+        }
+    }
+}
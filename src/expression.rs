@@ -0,0 +1,388 @@
+// for etc.
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// functions that can be used in expressions, angles are in degrees like
+// they are for rotate and arc
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("atan2", 2),
+    ("sqrt", 1),
+    ("abs", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("round", 1),
+    ("min", 2),
+    ("max", 2),
+    ("pow", 2),
+];
+
+// split a line into words
+//
+// words are split by whitespace, except for whitespace inside parentheses,
+// so rect ($x + 10) 0 10 10 has 5 words
+pub fn words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if !word.is_empty() {
+                    words.push(word.clone());
+                    word.clear();
+                }
+                continue;
+            }
+            _ => {}
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// the variables set with set commands
+pub struct Variables {
+    values: HashMap<String, f32>,
+}
+
+impl Variables {
+    pub fn new() -> Variables {
+        Variables {
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    // replace the arguments of a command that use variables ($x) or are in
+    // parentheses (($x + 10)) with their values
+    //
+    // only arguments that are numbers (going by the given function, which is
+    // given where each word is in the command) are worked out, so file names
+    // and titles are left as they are
+    //
+    // the value of a set command is always worked out, so set x 10*2 works
+    // only lines that are commands should be expanded, anything else is just
+    // printed as it is
+    pub fn expand(
+        &self,
+        command: &[String],
+        is_number: impl Fn(usize) -> bool,
+    ) -> Result<Vec<String>, String> {
+        let name = match command.first() {
+            Some(name) => name,
+            None => return Ok(vec![]),
+        };
+
+        let mut expanded = vec![name.clone()];
+        for (i, word) in command.iter().enumerate().skip(1) {
+            if !is_number(i) {
+                expanded.push(word.clone());
+            } else if (name == "set" && i == 2) || word.contains('$') || word.starts_with('(') {
+                expanded.push(self.eval(word)?.to_string());
+            } else {
+                expanded.push(word.clone());
+            }
+        }
+        Ok(expanded)
+    }
+
+    // work out the value of an expression
+    //
+    // expressions have numbers, variables, + - * / % ^, parentheses and
+    // functions like sin(45) or min($x, 10)
+    pub fn eval(&self, expression: &str) -> Result<f32, String> {
+        let mut parser = Parser {
+            chars: expression.chars().peekable(),
+            variables: self,
+        };
+        let value = parser.sum()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            Some(c) => Err(format!("unexpected `{}` in expression `{}`", c, expression)),
+            None => Ok(value),
+        }
+    }
+}
+
+// works out the value of an expression one character at a time
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    variables: &'a Variables,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    // the next character if it's one of the given ones
+    fn operator(&mut self, operators: &str) -> Option<char> {
+        self.skip_whitespace();
+        let c = *self.chars.peek()?;
+        if operators.contains(c) {
+            self.chars.next();
+            Some(c)
+        } else {
+            None
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.operator(&expected.to_string()) {
+            Some(_) => Ok(()),
+            None => Err(format!("expected `{}` in expression", expected)),
+        }
+    }
+
+    // a + b - c
+    fn sum(&mut self) -> Result<f32, String> {
+        let mut value = self.product()?;
+        while let Some(operator) = self.operator("+-") {
+            let right = self.product()?;
+            match operator {
+                '+' => value += right,
+                _ => value -= right,
+            }
+        }
+        Ok(value)
+    }
+
+    // a * b / c % d
+    fn product(&mut self) -> Result<f32, String> {
+        let mut value = self.negation()?;
+        while let Some(operator) = self.operator("*/%") {
+            let right = self.negation()?;
+            match operator {
+                '*' => value *= right,
+                '/' => value /= right,
+                _ => value %= right,
+            }
+        }
+        Ok(value)
+    }
+
+    // -a
+    fn negation(&mut self) -> Result<f32, String> {
+        match self.operator("-") {
+            Some(_) => Ok(-self.negation()?),
+            None => self.power(),
+        }
+    }
+
+    // a ^ b, which is worked out right to left
+    fn power(&mut self) -> Result<f32, String> {
+        let base = self.value()?;
+        match self.operator("^") {
+            Some(_) => Ok(base.powf(self.negation()?)),
+            None => Ok(base),
+        }
+    }
+
+    // a number, variable, function or something in parentheses
+    fn value(&mut self) -> Result<f32, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some('$') => {
+                self.chars.next();
+                let name = self.name();
+                match self.variables.values.get(&name) {
+                    Some(value) => Ok(*value),
+                    None if name.is_empty() => Err("expected name of variable after $".to_string()),
+                    None => Err(format!("variable `{}` is used before it is set", name)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.peek().copied() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    self.chars.next();
+                }
+                number
+                    .parse::<f32>()
+                    .map_err(|_| format!("`{}` is not a number", number))
+            }
+            Some(c) if c.is_alphabetic() => {
+                let name = self.name();
+                if name == "pi" {
+                    return Ok(std::f32::consts::PI);
+                }
+                let arity = match FUNCTIONS.iter().find(|(function, _)| *function == name) {
+                    Some((_, arity)) => *arity,
+                    None => return Err(format!("`{}` is not a function", name)),
+                };
+                self.expect('(')?;
+                let mut arguments = vec![self.sum()?];
+                while self.operator(",").is_some() {
+                    arguments.push(self.sum()?);
+                }
+                self.expect(')')?;
+                if arguments.len() != arity {
+                    return Err(format!(
+                        "expected {} arguments to {} but got {}",
+                        arity,
+                        name,
+                        arguments.len()
+                    ));
+                }
+                Ok(call(&name, &arguments))
+            }
+            Some(c) => Err(format!("unexpected `{}` in expression", c)),
+            None => Err("expected value in expression".to_string()),
+        }
+    }
+
+    // the name of a variable or function
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.chars.peek().copied() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            name.push(c);
+            self.chars.next();
+        }
+        name
+    }
+}
+
+// call one of the functions with the right number of arguments
+fn call(function: &str, arguments: &[f32]) -> f32 {
+    let a = arguments[0];
+    match function {
+        "sin" => a.to_radians().sin(),
+        "cos" => a.to_radians().cos(),
+        "tan" => a.to_radians().tan(),
+        "asin" => a.asin().to_degrees(),
+        "acos" => a.acos().to_degrees(),
+        "atan" => a.atan().to_degrees(),
+        "atan2" => a.atan2(arguments[1]).to_degrees(),
+        "sqrt" => a.sqrt(),
+        "abs" => a.abs(),
+        "floor" => a.floor(),
+        "ceil" => a.ceil(),
+        "round" => a.round(),
+        "min" => a.min(arguments[1]),
+        "max" => a.max(arguments[1]),
+        _ => a.powf(arguments[1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<f32, String> {
+        let mut variables = Variables::new();
+        variables.set("x", 3.0);
+        variables.eval(expression)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3.0));
+        assert_eq!(eval("12 / 3 / 2"), Ok(2.0));
+        assert_eq!(eval("7 % 4 + 1"), Ok(4.0));
+        assert_eq!(eval("$x * $x + 1"), Ok(10.0));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-$x"), Ok(-3.0));
+        assert_eq!(eval("2 * -3"), Ok(-6.0));
+        assert_eq!(eval("--2"), Ok(2.0));
+        assert_eq!(eval("1 - -1"), Ok(2.0));
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(eval("2 ^ 3"), Ok(8.0));
+        assert_eq!(eval("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(eval("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(eval("2 ^ -1"), Ok(0.5));
+        assert_eq!(eval("2 * 3 ^ 2"), Ok(18.0));
+    }
+
+    #[test]
+    fn functions() {
+        assert!((eval("sin(30)").unwrap() - 0.5).abs() < 1e-6);
+        assert!((eval("cos(60)").unwrap() - 0.5).abs() < 1e-6);
+        assert!((eval("atan2(1, 1)").unwrap() - 45.0).abs() < 1e-4);
+        assert_eq!(eval("sqrt(16)"), Ok(4.0));
+        assert_eq!(eval("abs(-$x)"), Ok(3.0));
+        assert_eq!(eval("floor(1.5) + ceil(1.5) + round(2.5)"), Ok(6.0));
+        assert_eq!(eval("min($x, 10)"), Ok(3.0));
+        assert_eq!(eval("max($x, 10)"), Ok(10.0));
+        assert_eq!(eval("pow(2, 10)"), Ok(1024.0));
+        assert_eq!(eval("pi"), Ok(std::f32::consts::PI));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("$y + 1"),
+            Err("variable `y` is used before it is set".to_string())
+        );
+        assert_eq!(
+            eval("$"),
+            Err("expected name of variable after $".to_string())
+        );
+        assert_eq!(eval("foo(1)"), Err("`foo` is not a function".to_string()));
+        assert_eq!(
+            eval("min(1)"),
+            Err("expected 2 arguments to min but got 1".to_string())
+        );
+        assert_eq!(
+            eval("(1 + 2"),
+            Err("expected `)` in expression".to_string())
+        );
+        assert_eq!(eval("1 +"), Err("expected value in expression".to_string()));
+        assert_eq!(eval("1.2.3"), Err("`1.2.3` is not a number".to_string()));
+        assert_eq!(
+            eval("1 2"),
+            Err("unexpected `2` in expression `1 2`".to_string())
+        );
+    }
+
+    #[test]
+    fn only_numbers_are_expanded() {
+        let variables = Variables::new();
+        let command = words("save ($x).png");
+        assert_eq!(variables.expand(&command, |_| false), Ok(command));
+        assert!(variables
+            .expand(&words("rect ($x) 0 1 1"), |_| true)
+            .is_err());
+    }
+
+    #[test]
+    fn words_in_parentheses() {
+        assert_eq!(words("rect ($x + 10) 0  10 10").len(), 5);
+    }
+}
@@ -0,0 +1,238 @@
+// for etc.
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+// how many files can be included inside each other before we give up
+pub const MAX_DEPTH: usize = 16;
+
+// the path of an include command (include path.txt), if the line is one
+pub fn parse(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let path = line.strip_prefix("include")?;
+    if path.starts_with(char::is_whitespace) {
+        Some(path.trim())
+    } else {
+        None
+    }
+}
+
+// a file that is being included
+struct Included {
+    // where the file really is, to check for files including themselves
+    resolved: PathBuf,
+    // the path to show when talking about the file
+    path: PathBuf,
+    reader: BufReader<File>,
+    // the number of lines read so far
+    line: usize,
+}
+
+// the files that have been included and that we are reading lines from
+//
+// the innermost file is read from until it runs out of lines, then we go
+// back to the file that included it
+pub struct Includes {
+    // the file the commands come from (if they come from a file), so that
+    // paths are relative to it and it can't include itself
+    base: Option<PathBuf>,
+    files: Vec<Included>,
+    // every file that has been included, even ones we are done reading
+    included: Vec<PathBuf>,
+}
+
+impl Includes {
+    pub fn new(base: Option<&str>) -> Includes {
+        Includes {
+            base: base.map(PathBuf::from),
+            files: vec![],
+            included: vec![],
+        }
+    }
+
+    // start reading lines from the given file
+    //
+    // the path is relative to the file doing the including (or the current
+    // directory if commands aren't from a file)
+    pub fn open(&mut self, path: &str) -> Result<(), String> {
+        if self.files.len() >= MAX_DEPTH {
+            return Err(format!(
+                "failed to include {}, files can only be included {} deep",
+                path, MAX_DEPTH
+            ));
+        }
+
+        let including = match self.files.last() {
+            Some(included) => Some(&included.path),
+            None => self.base.as_ref(),
+        };
+        let path = match including.and_then(|including| including.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let resolved = path
+            .canonicalize()
+            .map_err(|_| format!("failed to include {}, file not found", path.display()))?;
+
+        let base = self.base.as_ref().and_then(|base| base.canonicalize().ok());
+        if base.as_ref() == Some(&resolved)
+            || self
+                .files
+                .iter()
+                .any(|included| included.resolved == resolved)
+        {
+            return Err(format!(
+                "failed to include {}, it is already being included",
+                path.display()
+            ));
+        }
+
+        let file =
+            File::open(&resolved).map_err(|_| format!("failed to include {}", path.display()))?;
+        if !self.included.contains(&resolved) {
+            self.included.push(resolved.clone());
+        }
+        self.files.push(Included {
+            resolved,
+            path,
+            reader: BufReader::new(file),
+            line: 0,
+        });
+        Ok(())
+    }
+
+    // read a line from the innermost included file
+    //
+    // returns None if there are no included files left to read from
+    pub fn read_line(&mut self, buf: &mut String) -> Option<usize> {
+        while let Some(included) = self.files.last_mut() {
+            let read = included
+                .reader
+                .read_line(buf)
+                .expect("failed to read included file");
+            if read > 0 {
+                included.line += 1;
+                return Some(read);
+            }
+            self.files.pop();
+        }
+        None
+    }
+
+    // whether there are included files left to read from
+    pub fn is_reading(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    // every file that has been included so far
+    pub fn included(&self) -> &[PathBuf] {
+        &self.included
+    }
+
+    // the file and line number of the line that was just read
+    pub fn location(&self) -> Option<(&Path, usize)> {
+        self.files
+            .last()
+            .map(|included| (included.path.as_path(), included.line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // a directory of files for a test, named after the test so that tests
+    // running at the same time don't share one
+    fn files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stdg-include-{}", test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn parse_include() {
+        assert_eq!(parse("include a.txt\n"), Some("a.txt"));
+        assert_eq!(parse("  include  a b.txt "), Some("a b.txt"));
+        assert_eq!(parse("included a.txt"), None);
+        assert_eq!(parse("print include a.txt"), None);
+    }
+
+    #[test]
+    fn relative_paths() {
+        let dir = files(
+            "relative",
+            &[
+                ("main.txt", ""),
+                ("a.txt", "include b.txt\n"),
+                ("b.txt", "rect 0 0 1 1\n"),
+            ],
+        );
+        let mut includes = Includes::new(dir.join("main.txt").to_str());
+        includes.open("a.txt").unwrap();
+
+        let mut line = String::new();
+        includes.read_line(&mut line);
+        includes.open(parse(&line).unwrap()).unwrap();
+        line.clear();
+        includes.read_line(&mut line);
+        assert_eq!(line, "rect 0 0 1 1\n");
+        assert_eq!(includes.location(), Some((dir.join("b.txt").as_path(), 1)));
+    }
+
+    #[test]
+    fn cycles() {
+        let dir = files("cycles", &[("main.txt", ""), ("a.txt", ""), ("b.txt", "")]);
+        let mut includes = Includes::new(dir.join("main.txt").to_str());
+        includes.open("a.txt").unwrap();
+        includes.open("b.txt").unwrap();
+        assert!(includes
+            .open("a.txt")
+            .unwrap_err()
+            .ends_with("it is already being included"));
+        assert!(includes
+            .open("main.txt")
+            .unwrap_err()
+            .ends_with("it is already being included"));
+
+        // a file can be included again once it is done being included
+        let mut line = String::new();
+        assert_eq!(includes.read_line(&mut line), None);
+        includes.open("a.txt").unwrap();
+        let included = ["a.txt", "b.txt"].map(|name| dir.join(name).canonicalize().unwrap());
+        assert_eq!(includes.included(), included);
+    }
+
+    #[test]
+    fn max_depth() {
+        let names: Vec<String> = (0..=MAX_DEPTH).map(|i| format!("{}.txt", i)).collect();
+        let dir = files(
+            "depth",
+            &names
+                .iter()
+                .map(|name| (name.as_str(), ""))
+                .collect::<Vec<_>>(),
+        );
+        let mut includes = Includes::new(None);
+        for name in &names[..MAX_DEPTH] {
+            includes.open(dir.join(name).to_str().unwrap()).unwrap();
+        }
+        assert!(includes
+            .open(dir.join(&names[MAX_DEPTH]).to_str().unwrap())
+            .unwrap_err()
+            .ends_with(&format!("files can only be included {} deep", MAX_DEPTH)));
+    }
+
+    #[test]
+    fn missing() {
+        let mut includes = Includes::new(None);
+        assert!(includes
+            .open("no/such/file.txt")
+            .unwrap_err()
+            .ends_with("file not found"));
+    }
+}
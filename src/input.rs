@@ -0,0 +1,585 @@
+// for reading input from window
+use minifb::{InputCallback, Key, KeyRepeat, MouseButton, MouseMode, Window};
+// for etc.
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::command::{self, Query};
+
+// what happened in a window since the client last presented
+//
+// a window only knows what happened since it was last updated (which is
+// whenever we present or get input), so this is put together from every
+// update
+#[derive(Default)]
+pub struct Input {
+    pressed: Vec<Key>,
+    released: Vec<Key>,
+    text: String,
+    // how far the mouse wheel was scrolled since it was last asked about
+    wheel: (f32, f32),
+    // for each mouse button, whether it is down, whether it was clicked or
+    // double clicked and when and where it was last clicked (unless that
+    // click was already the second of a double click)
+    down: [bool; 3],
+    clicked: [bool; 3],
+    double_clicked: [bool; 3],
+    last_click: [Option<(Instant, (f32, f32))>; 3],
+    // characters typed into the window, if it is ours
+    typed: Typed,
+    // with events on, what happened is also sent to the client as events
+    // (like keydown a) each time it presents
+    push: bool,
+    events: Vec<String>,
+}
+
+impl Input {
+    // start tracking what is typed into a window
+    pub fn listen(&self, window: &mut Window) {
+        self.typed.listen(window);
+    }
+
+    // add what happened since the window was last updated, which has to be
+    // called after every update
+    pub fn track(&mut self, window: &Window) {
+        let update = Update::read(window, &self.typed);
+        self.add(&update);
+    }
+
+    // add what happened in an update of a window
+    pub fn add(&mut self, update: &Update) {
+        if self.push {
+            for key in &update.pressed {
+                self.events.push(format!("keydown {}", name(*key)));
+            }
+            for key in &update.released {
+                self.events.push(format!("keyup {}", name(*key)));
+            }
+            for c in update.text.chars() {
+                self.events.push(format!("char {}", c));
+            }
+            if let Some((dx, dy)) = update.scroll {
+                self.events.push(format!("scroll {} {}", dx, dy));
+            }
+        }
+        if let Some((dx, dy)) = update.scroll {
+            self.wheel.0 += dx;
+            self.wheel.1 += dy;
+        }
+
+        let (x, y) = update.mouse;
+        for (i, (down, (_, name))) in update.buttons.iter().zip(BUTTONS).enumerate() {
+            if *down && !self.down[i] {
+                self.clicked[i] = true;
+                let double = self.last_click[i].is_some_and(|(time, (last_x, last_y))| {
+                    update.time.duration_since(time) <= DOUBLE_CLICK_TIME
+                        && (x - last_x).hypot(y - last_y) <= DOUBLE_CLICK_DISTANCE
+                });
+                if double {
+                    self.double_clicked[i] = true;
+                    self.last_click[i] = None;
+                } else {
+                    self.last_click[i] = Some((update.time, (x, y)));
+                }
+                if self.push {
+                    self.events.push(format!("mousedown {} {} {}", name, x, y));
+                    if double {
+                        self.events
+                            .push(format!("doubleclick {} {} {}", name, x, y));
+                    }
+                }
+            } else if !*down && self.down[i] && self.push {
+                self.events.push(format!("mouseup {} {} {}", name, x, y));
+            }
+            self.down[i] = *down;
+        }
+        self.pressed.extend(&update.pressed);
+        self.released.extend(&update.released);
+        self.text.push_str(&update.text);
+    }
+
+    // the client presented, so what happens from now on is for the next
+    // present
+    pub fn present(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.text.clear();
+        self.clicked = [false; 3];
+        self.double_clicked = [false; 3];
+    }
+
+    // send events from now on (or stop sending them)
+    pub fn push(&mut self, push: bool) {
+        self.push = push;
+    }
+
+    // the events to send since they were last sent
+    pub fn events(&mut self) -> Vec<String> {
+        self.events.drain(..).collect()
+    }
+}
+
+// what happened in a window in one update
+pub struct Update {
+    pressed: Vec<Key>,
+    released: Vec<Key>,
+    text: String,
+    scroll: Option<(f32, f32)>,
+    // whether each mouse button is down, where the mouse is and when this
+    // was, for telling clicks from double clicks
+    buttons: Vec<bool>,
+    mouse: (f32, f32),
+    time: Instant,
+}
+
+impl Update {
+    // read what happened from a window that was just updated, along with
+    // what was typed into it
+    pub fn read(window: &Window, typed: &Typed) -> Update {
+        Update {
+            pressed: window.get_keys_pressed(KeyRepeat::No).unwrap_or_default(),
+            released: KEYS
+                .iter()
+                .map(|(key, _)| *key)
+                .filter(|key| window.is_key_released(*key))
+                .collect(),
+            text: typed.take(),
+            scroll: window.get_scroll_wheel(),
+            buttons: BUTTONS
+                .iter()
+                .map(|(button, _)| window.get_mouse_down(*button))
+                .collect(),
+            mouse: window.get_mouse_pos(MouseMode::Pass).unwrap_or((0.0, 0.0)),
+            time: Instant::now(),
+        }
+    }
+}
+
+// a second click of a mouse button is a double click if it is this soon
+// after the first and this close to it
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+const DOUBLE_CLICK_DISTANCE: f32 = 4.0;
+
+// characters typed into a window (with shift, the keyboard layout and so on
+// already worked out), which minifb tells us about with a callback
+//
+// control characters like enter and backspace are left out since they are
+// keys anyway
+#[derive(Clone, Default)]
+pub struct Typed(Rc<RefCell<String>>);
+
+impl Typed {
+    pub fn listen(&self, window: &mut Window) {
+        window.set_input_callback(Box::new(self.clone()));
+    }
+
+    fn take(&self) -> String {
+        self.0.borrow_mut().split_off(0)
+    }
+}
+
+impl InputCallback for Typed {
+    fn add_char(&mut self, uni_char: u32) {
+        if let Some(c) = std::char::from_u32(uni_char).filter(|c| !c.is_control()) {
+            self.0.borrow_mut().push(c);
+        }
+    }
+}
+
+// answer a get command by asking the window about mouse and keyboard input
+//
+// the window has to have been updated (and what happened tracked) first
+pub fn query(window: Option<(&Window, &mut Input)>, query: &Query) -> String {
+    // what stdg is and can do doesn't need a window
+    match query {
+        Query::Version => return command::VERSION.to_string(),
+        Query::Capabilities => return command::capabilities(),
+        _ => {}
+    }
+
+    let (window, input) = window.expect("window must be open to get input");
+
+    match query {
+        Query::MouseX => window
+            .get_mouse_pos(MouseMode::Pass)
+            .expect("failed to get x position of mouse")
+            .0
+            .to_string(),
+        Query::MouseY => window
+            .get_mouse_pos(MouseMode::Pass)
+            .expect("failed to get y position of mouse")
+            .1
+            .to_string(),
+        Query::Keys => window
+            .get_keys()
+            .expect("failed to get keys pressed")
+            .into_iter()
+            .map(name)
+            .collect::<Vec<&str>>()
+            .join(" "),
+        Query::KeysPressed => names(&input.pressed),
+        Query::KeysReleased => names(&input.released),
+        Query::TextInput => input.text.clone(),
+        Query::MouseWheel => {
+            let (dx, dy) = std::mem::take(&mut input.wheel);
+            format!("{} {}", dx, dy)
+        }
+        Query::MouseIsPressed(button) => window.get_mouse_down(*button).to_string(),
+        Query::MouseClicked(button) => input.clicked[index(*button)].to_string(),
+        Query::MouseDoubleClicked(button) => input.double_clicked[index(*button)].to_string(),
+        Query::KeyIsPressed(key) => window.is_key_down(*key).to_string(),
+        Query::Version | Query::Capabilities => unreachable!(),
+    }
+}
+
+// every mouse button and its name
+pub const BUTTONS: &[(MouseButton, &str)] = &[
+    (MouseButton::Left, "left"),
+    (MouseButton::Middle, "center"),
+    (MouseButton::Right, "right"),
+];
+
+// the mouse button with the given name
+pub fn button(name: &str) -> Option<MouseButton> {
+    BUTTONS
+        .iter()
+        .find(|(_, button_name)| *button_name == name)
+        .map(|(button, _)| *button)
+}
+
+// where a mouse button is in the list of buttons
+fn index(button: MouseButton) -> usize {
+    BUTTONS
+        .iter()
+        .position(|(other, _)| *other == button)
+        .unwrap_or_default()
+}
+
+// every key and its name, which is how it is sent back by get keys and
+// asked about with get keyispressed
+//
+// names are lowercase and never change once a key is here
+pub const KEYS: &[(Key, &str)] = &[
+    (Key::Key0, "0"),
+    (Key::Key1, "1"),
+    (Key::Key2, "2"),
+    (Key::Key3, "3"),
+    (Key::Key4, "4"),
+    (Key::Key5, "5"),
+    (Key::Key6, "6"),
+    (Key::Key7, "7"),
+    (Key::Key8, "8"),
+    (Key::Key9, "9"),
+    (Key::A, "a"),
+    (Key::B, "b"),
+    (Key::C, "c"),
+    (Key::D, "d"),
+    (Key::E, "e"),
+    (Key::F, "f"),
+    (Key::G, "g"),
+    (Key::H, "h"),
+    (Key::I, "i"),
+    (Key::J, "j"),
+    (Key::K, "k"),
+    (Key::L, "l"),
+    (Key::M, "m"),
+    (Key::N, "n"),
+    (Key::O, "o"),
+    (Key::P, "p"),
+    (Key::Q, "q"),
+    (Key::R, "r"),
+    (Key::S, "s"),
+    (Key::T, "t"),
+    (Key::U, "u"),
+    (Key::V, "v"),
+    (Key::W, "w"),
+    (Key::X, "x"),
+    (Key::Y, "y"),
+    (Key::Z, "z"),
+    (Key::F1, "f1"),
+    (Key::F2, "f2"),
+    (Key::F3, "f3"),
+    (Key::F4, "f4"),
+    (Key::F5, "f5"),
+    (Key::F6, "f6"),
+    (Key::F7, "f7"),
+    (Key::F8, "f8"),
+    (Key::F9, "f9"),
+    (Key::F10, "f10"),
+    (Key::F11, "f11"),
+    (Key::F12, "f12"),
+    (Key::F13, "f13"),
+    (Key::F14, "f14"),
+    (Key::F15, "f15"),
+    (Key::Up, "up"),
+    (Key::Down, "down"),
+    (Key::Left, "left"),
+    (Key::Right, "right"),
+    (Key::Space, "space"),
+    (Key::Tab, "tab"),
+    (Key::Enter, "enter"),
+    (Key::LeftShift, "leftshift"),
+    (Key::RightShift, "rightshift"),
+    (Key::Escape, "escape"),
+    (Key::Backspace, "backspace"),
+    (Key::Delete, "delete"),
+    (Key::LeftCtrl, "leftctrl"),
+    (Key::RightCtrl, "rightctrl"),
+    (Key::LeftAlt, "leftalt"),
+    (Key::RightAlt, "rightalt"),
+    (Key::LeftSuper, "leftsuper"),
+    (Key::RightSuper, "rightsuper"),
+    (Key::Home, "home"),
+    (Key::End, "end"),
+    (Key::PageUp, "pageup"),
+    (Key::PageDown, "pagedown"),
+    (Key::Insert, "insert"),
+    (Key::Menu, "menu"),
+    (Key::Pause, "pause"),
+    (Key::CapsLock, "capslock"),
+    (Key::NumLock, "numlock"),
+    (Key::ScrollLock, "scrolllock"),
+    (Key::Apostrophe, "apostrophe"),
+    (Key::Backquote, "backquote"),
+    (Key::Backslash, "backslash"),
+    (Key::Comma, "comma"),
+    (Key::Equal, "equal"),
+    (Key::LeftBracket, "leftbracket"),
+    (Key::Minus, "minus"),
+    (Key::Period, "period"),
+    (Key::RightBracket, "rightbracket"),
+    (Key::Semicolon, "semicolon"),
+    (Key::Slash, "slash"),
+    (Key::NumPad0, "numpad0"),
+    (Key::NumPad1, "numpad1"),
+    (Key::NumPad2, "numpad2"),
+    (Key::NumPad3, "numpad3"),
+    (Key::NumPad4, "numpad4"),
+    (Key::NumPad5, "numpad5"),
+    (Key::NumPad6, "numpad6"),
+    (Key::NumPad7, "numpad7"),
+    (Key::NumPad8, "numpad8"),
+    (Key::NumPad9, "numpad9"),
+    (Key::NumPadDot, "numpaddot"),
+    (Key::NumPadSlash, "numpadslash"),
+    (Key::NumPadAsterisk, "numpadasterisk"),
+    (Key::NumPadMinus, "numpadminus"),
+    (Key::NumPadPlus, "numpadplus"),
+    (Key::NumPadEnter, "numpadenter"),
+];
+
+// the key with the given name
+pub fn key(name: &str) -> Option<Key> {
+    KEYS.iter()
+        .find(|(_, key_name)| *key_name == name)
+        .map(|(key, _)| *key)
+}
+
+// the names of keys, each only once, separated by spaces
+fn names(keys: &[Key]) -> String {
+    let mut names: Vec<&str> = vec![];
+    for key in keys {
+        if !names.contains(&name(*key)) {
+            names.push(name(*key));
+        }
+    }
+    names.join(" ")
+}
+
+// the name of the given key
+pub fn name(key: Key) -> &'static str {
+    KEYS.iter()
+        .find(|(other, _)| *other == key)
+        .map_or("unknown", |(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an update where nothing happened
+    fn update(time: Instant) -> Update {
+        Update {
+            pressed: vec![],
+            released: vec![],
+            text: String::new(),
+            scroll: None,
+            buttons: vec![false; 3],
+            mouse: (0.0, 0.0),
+            time,
+        }
+    }
+
+    // an update with the left mouse button down (or up) at the given place
+    fn left(time: Instant, down: bool, mouse: (f32, f32)) -> Update {
+        Update {
+            buttons: vec![down, false, false],
+            mouse,
+            ..update(time)
+        }
+    }
+
+    // click the left mouse button at the given time after the start and place
+    fn click(input: &mut Input, start: Instant, after: u64, mouse: (f32, f32)) {
+        let time = start + Duration::from_millis(after);
+        input.add(&left(time, true, mouse));
+        input.add(&left(time, false, mouse));
+    }
+
+    #[test]
+    fn single_click() {
+        let mut input = Input::default();
+        let start = Instant::now();
+        input.add(&left(start, true, (10.0, 20.0)));
+        assert!(input.down[0]);
+        assert_eq!(input.clicked, [true, false, false]);
+        assert_eq!(input.double_clicked, [false; 3]);
+
+        // holding the button down isn't another click
+        input.present();
+        input.add(&left(start, true, (10.0, 20.0)));
+        input.add(&left(start, false, (10.0, 20.0)));
+        assert!(!input.down[0]);
+        assert_eq!(input.clicked, [false; 3]);
+    }
+
+    #[test]
+    fn double_click() {
+        let start = Instant::now();
+        let soon = DOUBLE_CLICK_TIME.as_millis() as u64;
+
+        let mut input = Input::default();
+        click(&mut input, start, 0, (10.0, 10.0));
+        click(
+            &mut input,
+            start,
+            soon,
+            (10.0 + DOUBLE_CLICK_DISTANCE, 10.0),
+        );
+        assert_eq!(input.double_clicked, [true, false, false]);
+
+        // too late
+        let mut input = Input::default();
+        click(&mut input, start, 0, (10.0, 10.0));
+        click(&mut input, start, soon + 1, (10.0, 10.0));
+        assert_eq!(input.double_clicked, [false; 3]);
+
+        // too far
+        let mut input = Input::default();
+        click(&mut input, start, 0, (10.0, 10.0));
+        click(
+            &mut input,
+            start,
+            10,
+            (10.0 + DOUBLE_CLICK_DISTANCE + 0.1, 10.0),
+        );
+        assert_eq!(input.double_clicked, [false; 3]);
+    }
+
+    #[test]
+    fn triple_click() {
+        // the third click of a triple click starts a new double click
+        // instead of being the second of another one
+        let mut input = Input {
+            push: true,
+            ..Input::default()
+        };
+        let start = Instant::now();
+        for after in [0, 100, 200] {
+            click(&mut input, start, after, (0.0, 0.0));
+        }
+        let events = input.events();
+        let doubles = events
+            .iter()
+            .filter(|event| event.starts_with("doubleclick"))
+            .count();
+        assert_eq!(doubles, 1);
+
+        click(&mut input, start, 300, (0.0, 0.0));
+        assert_eq!(input.events()[1], "doubleclick left 0 0");
+    }
+
+    #[test]
+    fn events_only_when_pushed() {
+        let mut input = Input::default();
+        let start = Instant::now();
+        let typing = Update {
+            pressed: vec![Key::A],
+            released: vec![Key::B],
+            text: "a ".to_string(),
+            scroll: Some((0.0, -3.0)),
+            ..update(start)
+        };
+        input.add(&typing);
+        click(&mut input, start, 0, (1.0, 2.0));
+        assert!(input.events().is_empty());
+
+        input.push(true);
+        input.add(&typing);
+        click(&mut input, start, 1000, (1.0, 2.0));
+        click(&mut input, start, 1100, (1.0, 2.0));
+        assert_eq!(
+            input.events(),
+            [
+                "keydown a",
+                "keyup b",
+                "char a",
+                "char  ",
+                "scroll 0 -3",
+                "mousedown left 1 2",
+                "mouseup left 1 2",
+                "mousedown left 1 2",
+                "doubleclick left 1 2",
+                "mouseup left 1 2",
+            ]
+        );
+        // events are only sent once
+        assert!(input.events().is_empty());
+
+        input.push(false);
+        input.add(&typing);
+        assert!(input.events().is_empty());
+    }
+
+    #[test]
+    fn edges() {
+        // keys pressed and released since the last present, each only once
+        let mut input = Input::default();
+        let start = Instant::now();
+        input.add(&Update {
+            pressed: vec![Key::A, Key::Space],
+            ..update(start)
+        });
+        input.add(&Update {
+            pressed: vec![Key::A],
+            released: vec![Key::A],
+            text: "hi".to_string(),
+            ..update(start)
+        });
+        assert_eq!(names(&input.pressed), "a space");
+        assert_eq!(names(&input.released), "a");
+        assert_eq!(input.text, "hi");
+
+        input.present();
+        assert_eq!(names(&input.pressed), "");
+        assert_eq!(names(&input.released), "");
+        assert_eq!(input.text, "");
+    }
+
+    #[test]
+    fn wheel() {
+        // scrolling adds up until it is asked about, even across presents
+        let mut input = Input::default();
+        let start = Instant::now();
+        for scroll in [(1.0, -3.0), (0.5, -2.0)] {
+            input.add(&Update {
+                scroll: Some(scroll),
+                ..update(start)
+            });
+        }
+        input.add(&update(start));
+        input.present();
+        assert_eq!(input.wheel, (1.5, -5.0));
+    }
+}
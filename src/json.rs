@@ -0,0 +1,357 @@
+// for reading and writing JSON
+use serde_json::{json, Value};
+
+use crate::command::{Query, COMMANDS};
+
+// the keys of the arguments of each command in JSON are the words in how the
+// command is used (in command::COMMANDS), in the order they are in a line
+//
+// {"cmd":"rect","x":1,"y":2,"width":3,"height":4} is the line rect 1 2 3 4
+//
+// keys in brackets are optional, as is a literal word (like as) that only
+// goes in the line if the key after it is there, and a key ending in ... is
+// an array of any number of arguments
+
+// keys that are true or false and are a word in the line if they are true,
+// like "forever":true for present forever
+const FLAGS: &[&str] = &["forever", "cancel"];
+
+// the key of the word that can be one of a few (like square|project|round
+// for strokecap), so that strokecap round is {"cmd":"strokecap","cap":"round"}
+const CHOICES: &[(&str, &str)] = &[
+    ("strokecap", "cap"),
+    ("strokejoin", "join"),
+    ("events", "state"),
+];
+
+// shorter keys that can be used instead, like
+// {"cmd":"rect","x":1,"y":2,"w":3,"h":4}
+const SHORT: &[(&str, &str)] = &[
+    ("width", "w"),
+    ("height", "h"),
+    ("red", "r"),
+    ("green", "g"),
+    ("blue", "b"),
+    ("alpha", "a"),
+];
+
+// the keys of the values of each event sent to the client in JSON, so that
+// the event keydown a is {"event":"keydown","key":"a"}
+const EVENTS: &[(&str, &str)] = &[
+    ("close", ""),
+    ("keydown", "key"),
+    ("keyup", "key"),
+    ("char", "char"),
+    ("scroll", "dx dy"),
+    ("mousedown", "button x y"),
+    ("mouseup", "button x y"),
+    ("doubleclick", "button x y"),
+];
+
+// the values of events that are always strings, even if they look like
+// numbers (like typing 5)
+const STRINGS: &[&str] = &["key", "char", "button"];
+
+// the lines a line of JSON is, which is either an object for one command or
+// an array of them
+//
+// returns None if the line isn't JSON (like [INFO] started), which is printed
+// like any other line that isn't a command
+pub fn lines(line: &str) -> Option<Result<Vec<String>, String>> {
+    let trimmed = line.trim_start();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        return None;
+    }
+    let value = serde_json::from_str::<Value>(line).ok()?;
+    let mut lines = vec![];
+    let commands = match value {
+        Value::Array(commands) => commands,
+        command => vec![command],
+    };
+    for command in &commands {
+        if let Err(message) = command_lines(command, &mut lines) {
+            return Some(Err(message));
+        }
+    }
+    Some(Ok(lines))
+}
+
+// the reply to a get command
+pub fn reply(query: &Query, answer: &str) -> String {
+    let value = match query {
+        Query::MouseX | Query::MouseY => answer.parse::<f64>().map_or(Value::Null, Value::from),
+        Query::Keys | Query::KeysPressed | Query::KeysReleased => {
+            Value::from(answer.split_whitespace().collect::<Vec<&str>>())
+        }
+        Query::MouseWheel => Value::from(
+            answer
+                .split_whitespace()
+                .filter_map(|delta| delta.parse::<f64>().ok())
+                .collect::<Vec<f64>>(),
+        ),
+        Query::MouseIsPressed(_)
+        | Query::KeyIsPressed(_)
+        | Query::MouseClicked(_)
+        | Query::MouseDoubleClicked(_) => Value::from(answer == "true"),
+        Query::Version | Query::TextInput => Value::from(answer),
+        // capabilities are words like commands=start,present,... which are
+        // an object of arrays
+        Query::Capabilities => Value::Object(
+            answer
+                .split_whitespace()
+                .filter_map(|word| word.split_once('='))
+                .map(|(key, values)| match key {
+                    "version" => (key.to_string(), Value::from(values)),
+                    _ => (
+                        key.to_string(),
+                        Value::from(values.split(',').collect::<Vec<&str>>()),
+                    ),
+                })
+                .collect(),
+        ),
+    };
+    json!({ "value": value }).to_string()
+}
+
+// an event sent to the client, where values that are numbers are numbers
+pub fn event(event: &str) -> String {
+    let name = event.split(' ').next().unwrap_or("");
+    let keys = EVENTS
+        .iter()
+        .find(|(event, _)| *event == name)
+        .map_or("", |(_, keys)| *keys);
+
+    // the last value is the rest of the event, so that typing a space is
+    // {"event":"char","char":" "}
+    let mut object = serde_json::Map::new();
+    object.insert("event".to_string(), Value::from(name));
+    let count = keys.split_whitespace().count();
+    for (key, word) in keys
+        .split_whitespace()
+        .zip(event.splitn(count + 1, ' ').skip(1))
+    {
+        let value = match word.parse::<f64>() {
+            Ok(number) if !STRINGS.contains(&key) => Value::from(number),
+            _ => Value::from(word),
+        };
+        object.insert(key.to_string(), value);
+    }
+    Value::Object(object).to_string()
+}
+
+// an error sent back instead of stdg stopping
+pub fn error(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
+
+// the lines of a command, which is one line except for text which has the
+// text to draw on the next line
+fn command_lines(command: &Value, lines: &mut Vec<String>) -> Result<(), String> {
+    let object = command
+        .as_object()
+        .ok_or_else(|| format!("expected object with cmd but got {}", command))?;
+    let name = object
+        .get("cmd")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| format!("expected cmd (string) in {}", command))?;
+    let usage = match COMMANDS.iter().find(|(command, _)| *command == name) {
+        Some((_, usage)) => *usage,
+        None => return Err(format!("`{}` is not a command", name)),
+    };
+
+    let is_set = |flag: &str| object.get(flag).and_then(|flag| flag.as_bool()) == Some(true);
+    let get = |key: &str| {
+        object.get(key).or_else(|| {
+            SHORT
+                .iter()
+                .find(|(long, _)| *long == key)
+                .and_then(|(_, short)| object.get(*short))
+        })
+    };
+    let mut line = name.to_string();
+    let mut literal = None;
+    let mut bracketed = false;
+    for key in usage.split_whitespace().skip(1) {
+        // a group of keys in brackets, like [width height], are optional
+        bracketed |= key.starts_with('[');
+        let optional = bracketed;
+        bracketed &= !key.ends_with(']');
+        let key = key.trim_matches(|c| c == '[' || c == ']');
+        if key == "as" {
+            literal = Some(key);
+            continue;
+        }
+        if FLAGS.contains(&key) {
+            if is_set(key) {
+                line.push(' ');
+                line.push_str(key);
+            }
+            continue;
+        }
+        if key.contains('|') {
+            let choice_key = CHOICES
+                .iter()
+                .find(|(command, _)| *command == name)
+                .map_or("choice", |(_, key)| *key);
+            match object.get(choice_key).and_then(|choice| choice.as_str()) {
+                Some(choice) if key.split('|').any(|word| word == choice) => {
+                    line.push(' ');
+                    line.push_str(choice);
+                }
+                None if optional => {}
+                _ => {
+                    return Err(format!(
+                        "expected {} to be one of {} in {}",
+                        choice_key,
+                        key.replace('|', ", "),
+                        command
+                    ))
+                }
+            }
+            continue;
+        }
+
+        let value = match get(key.trim_end_matches("...")) {
+            Some(value) => value,
+            None if optional => continue,
+            None => {
+                return Err(format!(
+                    "expected {} in {}",
+                    key.trim_end_matches("..."),
+                    command
+                ))
+            }
+        };
+        if let Some(literal) = literal.take() {
+            line.push(' ');
+            line.push_str(literal);
+        }
+        if key.ends_with("...") {
+            let values = value
+                .as_array()
+                .ok_or_else(|| format!("expected {} to be an array", key))?;
+            for value in values {
+                // points can be [x, y] pairs
+                match value.as_array() {
+                    Some(values) => {
+                        for value in values {
+                            push_word(&mut line, value, key)?;
+                        }
+                    }
+                    None => push_word(&mut line, value, key)?,
+                }
+            }
+        } else {
+            push_word(&mut line, value, key)?;
+        }
+    }
+    lines.push(line);
+
+    // the text to draw goes on the line after the text command
+    if name == "text" {
+        match object.get("text").and_then(|text| text.as_str()) {
+            Some(text) => lines.push(text.to_string()),
+            None => return Err(format!("expected text (string) in {}", command)),
+        }
+    }
+    Ok(())
+}
+
+// add a number or string to a line as a word
+fn push_word(line: &mut String, value: &Value, key: &str) -> Result<(), String> {
+    line.push(' ');
+    match value {
+        Value::Number(number) => line.push_str(&number.to_string()),
+        Value::String(string) => line.push_str(string),
+        _ => return Err(format!("expected {} to be a number or string", key)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(json: &str) -> Result<Vec<String>, String> {
+        lines(json).expect("expected line to be JSON")
+    }
+
+    #[test]
+    fn keys_are_words_of_usage() {
+        assert_eq!(
+            line(r#"{"cmd":"rect","x":1,"y":2,"width":3,"height":4}"#),
+            Ok(vec!["rect 1 2 3 4".to_string()])
+        );
+        assert_eq!(
+            line(r#"[{"cmd":"fill","red":1,"green":2,"blue":3},{"cmd":"present","forever":true}]"#),
+            Ok(vec![
+                "fill 1 2 3".to_string(),
+                "present forever".to_string()
+            ])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"open","file":"a.png","name":"a"}"#),
+            Ok(vec!["open a.png as a".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"image","name":"a","x":1,"y":2}"#),
+            Ok(vec!["image a 1 2".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"poly","points":[[1,2],[3,4]]}"#),
+            Ok(vec!["poly 1 2 3 4".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"text","x":1,"y":2,"text":"hi there"}"#),
+            Ok(vec!["text 1 2".to_string(), "hi there".to_string()])
+        );
+    }
+
+    #[test]
+    fn short_keys() {
+        assert_eq!(
+            line(r#"{"cmd":"rect","x":1,"y":2,"w":3,"h":4}"#),
+            Ok(vec!["rect 1 2 3 4".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"fill","r":1,"g":2,"b":3,"a":4}"#),
+            Ok(vec!["fill 1 2 3 4".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"image","name":"a","x":1,"y":2,"w":3,"h":4}"#),
+            Ok(vec!["image a 1 2 3 4".to_string()])
+        );
+    }
+
+    #[test]
+    fn choices() {
+        assert_eq!(
+            line(r#"{"cmd":"strokecap","cap":"round"}"#),
+            Ok(vec!["strokecap round".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"strokejoin","join":"bevel"}"#),
+            Ok(vec!["strokejoin bevel".to_string()])
+        );
+        assert_eq!(
+            line(r#"{"cmd":"events","state":"on"}"#),
+            Ok(vec!["events on".to_string()])
+        );
+        assert!(line(r#"{"cmd":"events"}"#).is_err());
+        assert!(line(r#"{"cmd":"strokecap","cap":"pointy"}"#).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(line(r#"{"cmd":"rect","x":1}"#).is_err());
+        assert!(line(r#"{"cmd":"nope"}"#).is_err());
+        assert!(line("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn not_json() {
+        assert!(lines("[INFO] server started").is_none());
+        assert!(lines("{not json").is_none());
+        assert!(lines("rect 1 2 3 4").is_none());
+    }
+}
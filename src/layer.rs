@@ -0,0 +1,452 @@
+// for displaying window
+use minifb::Window;
+// for etc.
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+use crate::client::Client;
+use crate::command::Query;
+use crate::display::Display;
+use crate::input::{self, Input, Typed, Update};
+use crate::session::Recorder;
+
+// with --listen, any number of clients can connect and each one draws on a
+// layer of its own
+//
+// each client is handled on its own thread exactly like a single client
+// would be, except that presenting sends what it drew to this thread where
+// every layer is drawn on top of each other (from the lowest z to the
+// highest, and in the order clients connected if they have the same z) and
+// presented in the one window
+//
+// a client names its layer and sets its z with layer NAME Z, and layers start
+// without a name at z 0
+//
+// the window is opened with the size and title of the first client to start
+pub fn serve(
+    address: &str,
+    configure: impl Fn(&mut Client) + Send + Sync + 'static,
+    recorder: Option<Recorder>,
+) {
+    let listener = Listener::bind(address);
+    eprintln!("listening on {}", address);
+
+    // only the first client is recorded
+    let mut recorder = recorder;
+    let (requests_in, requests) = mpsc::channel();
+    let open = Arc::new(AtomicBool::new(true));
+    let mut clients = 0;
+
+    let mut window: Option<Window> = None;
+    let typed = Typed::default();
+    let mut layers: Vec<Drawn> = vec![];
+    let mut buffer = vec![];
+    loop {
+        // let anyone who wants to draw connect
+        while let Some((input, output)) = listener.accept() {
+            let mut client = Client::stream(input, output);
+            configure(&mut client);
+            if let Some(recorder) = recorder.take() {
+                client.record(recorder);
+            }
+            let layer = Layer {
+                id: clients,
+                requests: requests_in.clone(),
+                open: open.clone(),
+                push: false,
+            };
+            clients += 1;
+            thread::spawn(move || draw(client, layer));
+        }
+
+        // handle whatever clients want, waiting a bit for them to want
+        // something so that we aren't spinning
+        let mut changed = false;
+        let mut request = requests.recv_timeout(Duration::from_millis(16)).ok();
+        while let Some(next) = request {
+            match next {
+                Request::Start(id, width, height, title, size) => {
+                    let window = window.get_or_insert_with(|| {
+                        let mut window = crate::open_window(width, height, &title);
+                        typed.listen(&mut window);
+                        window
+                    });
+                    let (width, height) = window.get_size();
+                    layers.push(Drawn {
+                        id,
+                        name: None,
+                        z: 0,
+                        data: vec![0; width * height],
+                        input: Input::default(),
+                    });
+                    sort(&mut layers);
+                    let _ = size.send((width, height));
+                }
+                Request::Present(id, data) => {
+                    if let Some(layer) = layers.iter_mut().find(|layer| layer.id == id) {
+                        layer.data = data;
+                        layer.input.present();
+                        changed = true;
+                    }
+                }
+                Request::Query(id, query, answer) => {
+                    // what happened is tracked for every layer, since the
+                    // window only knows what happened since it was updated
+                    if let Some(window) = &mut window {
+                        window.update();
+                        let update = Update::read(window, &typed);
+                        for layer in &mut layers {
+                            layer.input.add(&update);
+                        }
+                    }
+                    let input = layers.iter_mut().find(|layer| layer.id == id);
+                    let _ = answer.send(input::query(
+                        window.as_ref().zip(input.map(|layer| &mut layer.input)),
+                        &query,
+                    ));
+                }
+                Request::Push(id, push) => {
+                    if let Some(layer) = layers.iter_mut().find(|layer| layer.id == id) {
+                        layer.input.push(push);
+                    }
+                }
+                Request::Events(id, events) => {
+                    let layer = layers.iter_mut().find(|layer| layer.id == id);
+                    let _ = events.send(layer.map_or(vec![], |layer| layer.input.events()));
+                }
+                Request::Claim(id, name, z, claimed) => {
+                    // a layer is only ever owned by one client
+                    if layers
+                        .iter()
+                        .any(|layer| layer.id != id && layer.name.as_ref() == Some(&name))
+                    {
+                        let _ = claimed.send(false);
+                    } else {
+                        if let Some(layer) = layers.iter_mut().find(|layer| layer.id == id) {
+                            layer.name = Some(name);
+                            layer.z = z.unwrap_or(layer.z);
+                        }
+                        sort(&mut layers);
+                        changed = true;
+                        let _ = claimed.send(true);
+                    }
+                }
+                Request::Close(id) => {
+                    layers.retain(|layer| layer.id != id);
+                    changed = true;
+                }
+            }
+            request = requests.try_recv().ok();
+        }
+
+        if let Some(window) = &mut window {
+            if !window.is_open() {
+                break;
+            }
+            if changed || buffer.is_empty() {
+                let (width, height) = window.get_size();
+                composite(&layers, &mut buffer, width * height);
+            }
+            window.update_with_buffer(&buffer).unwrap();
+            let update = Update::read(window, &typed);
+            for layer in &mut layers {
+                layer.input.add(&update);
+            }
+        }
+    }
+
+    open.store(false, Ordering::SeqCst);
+    listener.close();
+}
+
+// a layer of the window that a client draws on
+//
+// this is what the client's thread has, everything about the window is
+// asked for from the thread the window is on
+pub struct Layer {
+    id: usize,
+    requests: Sender<Request>,
+    open: Arc<AtomicBool>,
+    // whether the client wants events, so that we only ask for them if so
+    push: bool,
+}
+
+impl Layer {
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    // the size of the window, which is opened if this is the first client
+    // to start
+    fn start(&self, width: usize, height: usize, title: &str) -> (usize, usize) {
+        let (size_in, size) = mpsc::channel();
+        let _ = self.requests.send(Request::Start(
+            self.id,
+            width,
+            height,
+            title.to_string(),
+            size_in,
+        ));
+        size.recv().expect("window was closed")
+    }
+
+    pub fn present(&self, canvas: &Canvas) {
+        let _ = self
+            .requests
+            .send(Request::Present(self.id, canvas.data().to_vec()));
+    }
+
+    pub fn wait_until_closed(&self) {
+        while self.is_open() {
+            thread::sleep(Duration::from_millis(16));
+        }
+    }
+
+    pub fn query(&self, query: Query) -> String {
+        let (answer_in, answer) = mpsc::channel();
+        let _ = self
+            .requests
+            .send(Request::Query(self.id, query, answer_in));
+        answer.recv().expect("window was closed")
+    }
+
+    pub fn push(&mut self, push: bool) {
+        self.push = push;
+        let _ = self.requests.send(Request::Push(self.id, push));
+    }
+
+    pub fn events(&self) -> Vec<String> {
+        if !self.push {
+            return vec![];
+        }
+        let (events_in, events) = mpsc::channel();
+        let _ = self.requests.send(Request::Events(self.id, events_in));
+        events.recv().expect("window was closed")
+    }
+
+    // name the layer and move it above or below the others, returns false if
+    // another client already has a layer with the name
+    pub fn claim(&self, name: &str, z: Option<i32>) -> bool {
+        let (claimed_in, claimed) = mpsc::channel();
+        let _ = self
+            .requests
+            .send(Request::Claim(self.id, name.to_string(), z, claimed_in));
+        claimed.recv().expect("window was closed")
+    }
+}
+
+// a layer goes away once its client is done, even if the client panicked
+impl Drop for Layer {
+    fn drop(&mut self) {
+        let _ = self.requests.send(Request::Close(self.id));
+    }
+}
+
+// what a client's thread asks of the thread with the window
+enum Request {
+    Start(usize, usize, usize, String, Sender<(usize, usize)>),
+    Present(usize, Vec<u32>),
+    Query(usize, Query, Sender<String>),
+    Push(usize, bool),
+    Events(usize, Sender<Vec<String>>),
+    Claim(usize, String, Option<i32>, Sender<bool>),
+    Close(usize),
+}
+
+// what a client last presented on its layer
+struct Drawn {
+    id: usize,
+    name: Option<String>,
+    z: i32,
+    data: Vec<u32>,
+    input: Input,
+}
+
+// handle a client on its own thread
+fn draw(mut client: Client, layer: Layer) {
+    let (width, height, title) = crate::start(&mut client);
+    let (width, height) = layer.start(width, height, &title);
+    let mut canvas = Canvas::new(width, height);
+    let mut display = Display::Layer(layer);
+    crate::run(&mut client, &mut display, &mut canvas);
+}
+
+// put layers in the order they are drawn in
+fn sort(layers: &mut [Drawn]) {
+    layers.sort_by_key(|layer| (layer.z, layer.id));
+}
+
+// draw each layer on top of the ones before it
+//
+// pixels are premultiplied ARGB, so a pixel is drawn over another by adding
+// it to what is left of the other after covering it
+fn composite(layers: &[Drawn], buffer: &mut Vec<u32>, len: usize) {
+    buffer.clear();
+    buffer.resize(len, 0);
+    for layer in layers {
+        for (below, above) in buffer.iter_mut().zip(&layer.data) {
+            let alpha = above >> 24;
+            *below = match alpha {
+                255 => *above,
+                0 => *below,
+                _ => {
+                    let mut pixel = 0;
+                    for shift in [0, 8, 16, 24] {
+                        let above = (above >> shift) & 0xff;
+                        let below = (*below >> shift) & 0xff;
+                        let channel = above + below * (255 - alpha) / 255;
+                        pixel |= channel.min(255) << shift;
+                    }
+                    pixel
+                }
+            };
+        }
+    }
+}
+
+// a socket we are listening on for clients
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, String),
+}
+
+impl Listener {
+    // listen on unix:/path/to/socket or tcp:host:port
+    fn bind(address: &str) -> Listener {
+        let listener = if let Some(address) = address.strip_prefix("tcp:") {
+            Listener::Tcp(TcpListener::bind(address).expect("failed to listen on address"))
+        } else if let Some(path) = address.strip_prefix("unix:") {
+            Listener::unix(path)
+        } else {
+            panic!("expected address like unix:/tmp/stdg.sock or tcp:127.0.0.1:7070");
+        };
+        match &listener {
+            Listener::Tcp(listener) => listener.set_nonblocking(true),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.set_nonblocking(true),
+        }
+        .expect("failed to listen on address");
+        listener
+    }
+
+    #[cfg(unix)]
+    fn unix(path: &str) -> Listener {
+        // a socket left over from last time would stop us from listening
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            std::fs::remove_file(path).expect("failed to remove old socket");
+        }
+        Listener::Unix(
+            UnixListener::bind(path).expect("failed to listen on socket"),
+            path.to_string(),
+        )
+    }
+
+    #[cfg(not(unix))]
+    fn unix(_path: &str) -> Listener {
+        panic!("unix sockets aren't supported on this platform, use tcp:host:port instead");
+    }
+
+    // a client that has connected, if there is one
+    #[allow(clippy::type_complexity)]
+    fn accept(&self) -> Option<(Box<dyn Write + Send>, Box<dyn BufRead + Send>)> {
+        let accepted: std::io::Result<(Box<dyn Write + Send>, Box<dyn BufRead + Send>)> = match self
+        {
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                Ok((
+                    Box::new(stream.try_clone()?) as Box<dyn Write + Send>,
+                    Box::new(BufReader::new(stream)) as Box<dyn BufRead + Send>,
+                ))
+            }),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().and_then(|(stream, _)| {
+                stream.set_nonblocking(false)?;
+                Ok((
+                    Box::new(stream.try_clone()?) as Box<dyn Write + Send>,
+                    Box::new(BufReader::new(stream)) as Box<dyn BufRead + Send>,
+                ))
+            }),
+        };
+        match accepted {
+            Ok(accepted) => Some(accepted),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => None,
+            Err(error) => panic!("failed to accept connection: {}", error),
+        }
+    }
+
+    // stop listening, removing the socket file if there is one
+    fn close(self) {
+        #[cfg(unix)]
+        {
+            if let Listener::Unix(_, path) = self {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(id: usize, z: i32, data: Vec<u32>) -> Drawn {
+        Drawn {
+            id,
+            name: None,
+            z,
+            data,
+            input: Input::default(),
+        }
+    }
+
+    #[test]
+    fn order() {
+        let mut layers = vec![
+            layer(0, 1, vec![]),
+            layer(1, -1, vec![]),
+            layer(2, 0, vec![]),
+            layer(3, 1, vec![]),
+            layer(4, 0, vec![]),
+        ];
+        sort(&mut layers);
+        let order: Vec<usize> = layers.iter().map(|layer| layer.id).collect();
+        assert_eq!(order, [1, 2, 4, 0, 3]);
+    }
+
+    #[test]
+    fn over() {
+        let blue = 0xff0000ff;
+        let mut buffer = vec![];
+
+        // opaque pixels cover what is below and transparent ones don't
+        let layers = [
+            layer(0, 0, vec![blue, blue, blue]),
+            layer(1, 0, vec![0xffff0000, 0x00000000, 0x00ffffff]),
+        ];
+        composite(&layers, &mut buffer, 3);
+        assert_eq!(buffer, [0xffff0000, blue, blue]);
+
+        // half of red over blue is half of each
+        let layers = [layer(0, 0, vec![blue]), layer(1, 0, vec![0x80800000])];
+        composite(&layers, &mut buffer, 1);
+        assert_eq!(buffer, [0xff80007f]);
+
+        // half of red over nothing is still half of red
+        composite(&layers[1..], &mut buffer, 1);
+        assert_eq!(buffer, [0x80800000]);
+
+        // the buffer starts out transparent each time, even where a layer
+        // hasn't presented anything yet
+        composite(&[layer(0, 0, vec![])], &mut buffer, 2);
+        assert_eq!(buffer, [0, 0]);
+    }
+}
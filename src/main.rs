@@ -1,26 +1,39 @@
+mod binary;
+mod canvas;
+mod check;
+mod client;
+mod command;
+mod display;
+mod expression;
+mod include;
+mod input;
+mod json;
+mod layer;
+mod repl;
+mod restart;
+mod run;
+mod script;
+mod session;
+mod test;
+mod windows;
+
 // for displaying window
-use minifb::{Key, MouseButton, MouseMode, Scale, Window, WindowOptions};
-// for drawing graphics to window
-use raqote::{
-    DrawOptions, DrawTarget, Image, LineCap, LineJoin, PathBuilder, Point, SolidSource, Source,
-    StrokeStyle, Transform,
-};
-// for computing transformations
-use euclid::Angle;
-// for fonts
-use font_kit::loaders::default::Font;
+use minifb::{Scale, Window, WindowOptions};
 // for etc.
-use std::collections::HashMap;
+use std::cell::Cell;
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Command, Stdio};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+use std::thread;
+use std::time::Duration;
 
-macro_rules! is_open {
-    ($window:expr) => {
-        $window.is_open()
-    };
-}
+use canvas::Canvas;
+use client::{Client, Launch};
+use command::{Command, Error};
+use display::Display;
+use script::Script;
+use session::{Recorder, Replay};
+use windows::Windows;
 
 // here's how stdg can be used by a client
 //
@@ -57,958 +70,397 @@ fn main() {
     // ok, on to the code...
 
     // accept command line arguments
+    // options come first, anything after them is the process to launch
     let args: Vec<String> = env::args().collect();
 
-    // if we have an argument passed in, this is the process to launch
-    // so, we launch the given process
-    // then, get handles to stdin and stdout to and from the
-    //
-    // otherwise, we just get a handle to stdin to read from
-    let mut process = if args.len() >= 2 {
-        Some(
-            Command::new(&args[1])
-                .args(&args[2..])
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("failed to execute process"),
-        )
-    } else {
-        None
-    };
-    let (mut process_in, mut process_out_reader, mut stdin_reader) = if args.len() >= 2 {
-        (
-            Some(
-                process
-                    .as_mut()
-                    .unwrap()
-                    .stdin
-                    .take()
-                    .expect("failed to get stdin to process"),
-            ),
-            Some(BufReader::new(
-                process
-                    .as_mut()
-                    .unwrap()
-                    .stdout
-                    .take()
-                    .expect("failed to get stdout from process"),
-            )),
-            Some(std::io::stdin()),
-        )
-    } else {
-        (None, None, Some(std::io::stdin()))
+    // some things stdg can do don't need a client at all
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("test") => return test::main(&args[2..]),
+        Some("check") => return check::main(&args[2..]),
+        Some("repl") => return repl::main(&args[2..]),
+        Some("run") => return run::main(&args[2..]),
+        _ => {}
+    }
+
+    let mut record_session = None;
+    let mut strip_comments = false;
+    let mut prefix = None;
+    let mut json = false;
+    let mut keep_open = false;
+    let mut grace_period = client::GRACE_PERIOD;
+    let mut launch = Launch::default();
+    let mut restart_on_exit = false;
+    let mut watch = None;
+    let mut listen = None;
+    let mut replay = None;
+    let mut i = 1;
+    while i < args.len() && args[i].starts_with("--") {
+        match args[i].as_str() {
+            "--record-session" => {
+                record_session = Some(
+                    args.get(i + 1)
+                        .expect("expected file to record session to")
+                        .clone(),
+                );
+                i += 1;
+            }
+            "--strip-comments" => strip_comments = true,
+            "--json" => json = true,
+            "--keep-open" => keep_open = true,
+            "--cwd" => {
+                launch.cwd = Some(
+                    args.get(i + 1)
+                        .expect("expected directory to run process in")
+                        .clone(),
+                );
+                i += 1;
+            }
+            "--env" => {
+                let variable = args
+                    .get(i + 1)
+                    .expect("expected KEY=VALUE to set in environment of process");
+                let (key, value) = variable
+                    .split_once('=')
+                    .expect("expected KEY=VALUE to set in environment of process");
+                launch.env.push((key.to_string(), value.to_string()));
+                i += 1;
+            }
+            "--shell" => launch.shell = true,
+            "--restart-on-exit" => restart_on_exit = true,
+            "--watch" => {
+                watch = Some(
+                    args.get(i + 1)
+                        .expect("expected directory to watch for changes")
+                        .clone(),
+                );
+                i += 1;
+            }
+            "--grace-period" => {
+                grace_period = Duration::from_secs_f32(
+                    args.get(i + 1)
+                        .expect("expected seconds to wait for process to close")
+                        .parse::<f32>()
+                        .expect("expected seconds (f32) to wait for process to close"),
+                );
+                i += 1;
+            }
+            "--listen" => {
+                listen = Some(
+                    args.get(i + 1)
+                        .expect("expected address to listen on")
+                        .clone(),
+                );
+                i += 1;
+            }
+            "--prefix" => prefix = Some(command::PREFIX.to_string()),
+            option if option.starts_with("--prefix=") => {
+                prefix = Some(option["--prefix=".len()..].to_string())
+            }
+            "--replay" => {
+                replay = Some(
+                    args.get(i + 1)
+                        .expect("expected file to replay session from")
+                        .clone(),
+                );
+                i += 1;
+            }
+            _ => panic!("unsupported option {}", args[i]),
+        }
+        i += 1;
+    }
+
+    // a process that is launched again keeps the window, so it is never kept
+    // open once it's done
+    let restart = restart_on_exit || watch.is_some();
+    let keep_open = keep_open && !restart;
+
+    // options that apply to every client
+    let configure = move |client: &mut Client| {
+        if strip_comments {
+            client.strip_comments();
+        }
+        if let Some(prefix) = &prefix {
+            client.require_prefix(prefix.clone());
+        }
+        if json {
+            client.json();
+        }
+        if keep_open {
+            client.keep_open();
+        }
+        client.grace_period(grace_period);
     };
+    let recorder = record_session.as_ref().map(|path| Recorder::create(path));
 
-    // keep track of what we have read so far
-    let mut reading = String::new();
+    // if we are listening, whatever connects is a client with a layer of the
+    // window to draw on
+    if let Some(address) = &listen {
+        layer::serve(address, configure, recorder);
+        return;
+    }
 
-    // get window command
-    if process.is_some() {
-        process_out_reader
-            .as_mut()
-            .unwrap()
-            .read_line(&mut reading)
-            .expect("expected start command");
+    if restart {
+        launch.command = args
+            .get(i..)
+            .filter(|command| !command.is_empty())
+            .expect("expected process to restart")
+            .to_vec();
+        std::process::exit(restart::main(
+            &launch,
+            configure,
+            recorder,
+            restart_on_exit,
+            watch.as_deref(),
+        ));
+    }
+
+    // if we are replaying a session, the session is the client
+    // if we have a process passed in, we launch it and it is the client
+    // otherwise, we just read from stdin
+    let mut client = if let Some(path) = &replay {
+        Client::replay(Replay::open(path))
+    } else if i < args.len() {
+        launch.command = args[i..].to_vec();
+        Client::spawn(&launch)
     } else {
-        stdin_reader
-            .as_mut()
-            .unwrap()
-            .read_line(&mut reading)
-            .expect("expected start command");
+        Client::stdin()
+    };
+    configure(&mut client);
+    if let Some(recorder) = recorder {
+        client.record(recorder);
     }
-    let window_info = reading
-        .split_whitespace()
-        .map(|token| token.to_string())
-        .collect::<Vec<String>>();
-    reading.clear();
-    assert_eq!(
-        window_info.get(0).expect("expected start command").as_str(),
-        "start"
-    );
-    let window_width = window_info
-        .get(1)
-        .expect("expected width of window")
-        .parse::<usize>()
-        .expect("expected integer (usize) width of window");
-    let window_height = window_info
-        .get(2)
-        .expect("expected height of window")
-        .parse::<usize>()
-        .expect("expected integer (usize) height of window");
-    let window_title = window_info
-        .get(3..)
-        .expect("expected title of window")
-        .join(" ");
+
+    // get window command
+    let (window_width, window_height, window_title) = start(&mut client);
 
     // create the window
-    // initialize the window as draw target
-    // intialize window width and height
-    let mut window = Window::new(
-        window_title.as_str(),
-        window_width,
-        window_height,
+    // a replayed session already has all the input it needs, so it is drawn
+    // without a window
+    let mut display = if client.is_replay() {
+        Display::Headless
+    } else {
+        Display::window(window_width, window_height, &window_title)
+    };
+
+    // initialize the canvas as draw target
+    let mut canvas = Canvas::new(window_width, window_height);
+
+    // draw forever
+    run(&mut client, &mut display, &mut canvas);
+
+    // stdg exits however the process it launched did, so that it can be
+    // used in scripts
+    std::process::exit(client.close());
+}
+
+// create a window to present a canvas of the given size in
+pub fn open_window(width: usize, height: usize, title: &str) -> Window {
+    Window::new(
+        title,
+        width,
+        height,
         WindowOptions {
             resize: false,
             scale: Scale::X1,
             ..WindowOptions::default()
         },
     )
-    .expect("failed to create window");
-    let mut draw_target = DrawTarget::new(window_width as i32, window_height as i32);
-
-    // keep track of some stuff
-    let mut fill: Option<(u8, u8, u8, u8)> = None;
-    let mut stroke = Some((0, 0, 0, 255));
-    let mut stroke_weight = 1.0;
-    let mut stroke_cap = LineCap::Round;
-    let mut stroke_join = LineJoin::Miter;
-    let mut transformations = vec![draw_target.get_transform().clone()];
-    let mut text_font = None;
-    let mut text_size = None;
-    let mut images: HashMap<String, _> = HashMap::new();
+    .expect("failed to create window")
+}
 
-    // draw forever
-    while is_open!(window) {
-        // read a line from process
-        if process.is_some() {
-            process_out_reader
-                .as_mut()
-                .unwrap()
-                .read_line(&mut reading)
-                .expect("expected command to draw graphics");
-        } else {
-            stdin_reader
-                .as_mut()
-                .unwrap()
-                .read_line(&mut reading)
-                .expect("expected command to draw graphics");
+// read the start command, the first line printed by any client
+//
+// returns the width, height and title of the window
+pub fn start(client: &mut Client) -> (usize, usize, String) {
+    let mut reading = String::new();
+    client.read_line(&mut reading);
+    // the start line can have the prefix like any other command, but it's
+    // always taken as the start line
+    let line = client.strip_prefix(&reading).unwrap_or(&reading);
+    let start = command::Start::parse(line).unwrap_or_else(|message| panic!("{}", message));
+    if start.binary {
+        client.binary();
+    }
+    (start.width, start.height, start.title)
+}
+
+// handle commands from the client until the window is closed
+//
+// without a window of its own (like when replaying a session, testing a
+// script or drawing on a layer), commands are handled until there are no more
+pub fn run(client: &mut Client, display: &mut Display, canvas: &mut Canvas) {
+    // keep track of what we have read so far
+    let mut reading = String::new();
+    let mut script = Script::new();
+    let mut windows = Windows::new();
+
+    loop {
+        // once the main window is closed, the process we launched is asked
+        // to close and we keep handling what it prints until it exits (or
+        // is killed) or cancels closing
+        if !windows.is_open(display) && !client.is_closing() && !client.ask_to_close() {
+            break;
         }
-        let command = reading
-            .split_whitespace()
-            .map(|token| token.to_string())
-            .collect::<Vec<String>>();
+
+        // read a line from process (or from a block being run)
+        // we are done when we run out of lines, although what was drawn last
+        // can be kept open until the window is closed
+        let collecting = script.is_collecting();
+        let read = script.read_line(&mut reading, |reading| {
+            match wait(client, display, canvas, &mut windows, false, collecting) {
+                true => client.read_line(reading),
+                false => 0,
+            }
+        });
+        if read == 0 {
+            if script.is_collecting() {
+                client.error("expected end of repeat, for or define");
+            }
+            if client.keeps_open() {
+                windows.present_forever(display, canvas);
+            }
+            break;
+        }
+        // with binary framing, a command sent as numbers is made straight
+        // from its numbers (unless it's part of a block, which is lines)
+        let framed = match client.frame() {
+            Some((opcode, numbers)) if script.is_collecting() => {
+                reading = binary::line(opcode, numbers);
+                None
+            }
+            Some((opcode, numbers)) => Some(Command::decode(opcode, numbers)),
+            None => None,
+        };
+        let (command, parsed) = match framed {
+            Some(parsed) => (vec![], parsed),
+            None => {
+                // a line that can't be a command (because it doesn't have the
+                // prefix we were told to require) has no words to parse and is
+                // printed
+                let line = client.strip_prefix(&reading).unwrap_or("");
+                let command = expression::words(line);
+                // lines inside a block are run when the block ends
+                if script.collect(&reading, &command) {
+                    reading.clear();
+                    continue;
+                }
+                // what is printed is printed as it is, without working out
+                // anything in it
+                if let Some(text) = command::print_text(line) {
+                    println!("{}", text);
+                    reading.clear();
+                    continue;
+                }
+                // arguments that use variables are replaced by their values
+                match script.expand(&command) {
+                    Ok(command) => {
+                        let parsed = Command::parse(&command);
+                        (command, parsed)
+                    }
+                    Err(message) => (command, Err(Error::Invalid(message))),
+                }
+            }
+        };
+
+        // a line that isn't from a file and doesn't have the prefix may just
+        // be output that looks like a command, which is printed if it turns
+        // out not to be a valid one
+        let maybe_output = !command.is_empty() && !client.is_meant_as_command(&reading);
 
         // handle the command from line
-        if command.len() >= 3 && command.len() % 2 == 1 && command[0].as_str() == "poly" {
-            let mut pb = PathBuilder::new();
-
-            let mut is_first = true;
-            for point in command[1..].chunks(2) {
-                if is_first {
-                    is_first = false;
-                    pb.move_to(
-                        point[0]
-                            .parse::<f32>()
-                            .expect("expected x position (f32) of vertex of polygon"),
-                        point[1]
-                            .parse::<f32>()
-                            .expect("expected y position (f32) of vertex of polygon"),
-                    );
-                } else {
-                    pb.line_to(
-                        point[0]
-                            .parse::<f32>()
-                            .expect("expected x position (f32) of vertex of polygon"),
-                        point[1]
-                            .parse::<f32>()
-                            .expect("expected y position (f32) of vertex of polygon"),
-                    );
+        match parsed {
+            Ok(Command::Present) => {
+                display.present(canvas);
+                windows.update();
+                for event in display.events() {
+                    client.event(&event);
                 }
             }
-            let path = pb.finish();
-
-            if let Some(fill_color) = &fill {
-                let source = Source::Solid(SolidSource {
-                    r: fill_color.0,
-                    g: fill_color.1,
-                    b: fill_color.2,
-                    a: 255,
+            // the client can still print things once the window is closed,
+            // like when it is asked to close
+            Ok(Command::PresentForever) => windows.present_forever(display, canvas),
+            Ok(Command::Get(query)) => {
+                let json = client.is_json();
+                client.reply(|| {
+                    let answer = display.query(query.clone());
+                    if json {
+                        json::reply(&query, &answer)
+                    } else {
+                        answer
+                    }
                 });
-                draw_target.fill(
-                    &path,
-                    &source,
-                    &DrawOptions {
-                        alpha: fill_color.3 as f32 / 255.0,
-                        ..DrawOptions::default()
-                    },
-                );
             }
-            if let Some(stroke_color) = &stroke {
-                let source = Source::Solid(SolidSource {
-                    r: stroke_color.0,
-                    g: stroke_color.1,
-                    b: stroke_color.2,
-                    a: stroke_color.3,
+            Ok(Command::Text(x, y)) => {
+                // the text to draw is on the next line
+                let mut text_to_draw = String::new();
+                script.read_line(&mut text_to_draw, |text| {
+                    match wait(client, display, canvas, &mut windows, true, false) {
+                        true => client.read_text(text),
+                        false => 0,
+                    }
                 });
-                draw_target.stroke(
-                    &path,
-                    &source,
-                    &StrokeStyle {
-                        width: stroke_weight,
-                        cap: stroke_cap,
-                        join: stroke_join,
-                        ..StrokeStyle::default()
-                    },
-                    &DrawOptions::default(),
-                );
+                draw(client, || canvas.text(x, y, &text_to_draw));
             }
-        } else {
-            match command.len() {
-                1 => match command[0].as_str() {
-                    "present" => {
-                        // draw everything
-                        window.update_with_buffer(draw_target.get_data()).unwrap();
-                    }
-                    "nofill" => {
-                        fill = None;
-                    }
-                    "nostroke" => {
-                        stroke = None;
-                    }
-                    "push" => {
-                        if transformations.len() == 0 {
-                            panic!("invalid usage of push and pop");
-                        }
-                        transformations.push(transformations.last().unwrap().clone());
-                    }
-                    "pop" => {
-                        if transformations.len() == 0 {
-                            panic!("no transformation to pop");
-                        }
-                        transformations.pop();
-                        if transformations.len() == 0 {
-                            panic!("pop was not expected");
-                        }
-                        draw_target.set_transform(transformations.last().unwrap());
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                2 => match command[0].as_str() {
-                    "present" => match command[1].as_str() {
-                        "forever" => {
-                            while is_open!(window) {
-                                // draw everything
-                                window.update_with_buffer(draw_target.get_data()).unwrap();
-                            }
-                            break;
-                        }
-                        _ => {
-                            panic!("unsupported usage of present command");
-                        }
-                    },
-                    "get" => match command[1].as_str() {
-                        "mousex" => {
-                            window.update();
-                            writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for mouse x position to be printed to it"), "{}", window.get_mouse_pos(MouseMode::Pass).expect("failed to get x position of mouse").0);
-                        }
-                        "mousey" => {
-                            window.update();
-                            writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for mouse y position to be printed to it"), "{}", window.get_mouse_pos(MouseMode::Pass).expect("failed to get y position of mouse").1);
-                        }
-                        "keys" => {
-                            window.update();
-                            let mut output = String::new();
-                            for key in window.get_keys().expect("failed to get keys pressed") {
-                                if output.len() > 0 {
-                                    output += " ";
-                                }
-                                output += match key {
-                                    Key::Key0 => "0",
-                                    Key::Key1 => "1",
-                                    Key::Key2 => "2",
-                                    Key::Key3 => "3",
-                                    Key::Key4 => "4",
-                                    Key::Key5 => "5",
-                                    Key::Key6 => "6",
-                                    Key::Key7 => "7",
-                                    Key::Key8 => "8",
-                                    Key::Key9 => "9",
-                                    Key::A => "a",
-                                    Key::B => "b",
-                                    Key::C => "c",
-                                    Key::D => "d",
-                                    Key::E => "e",
-                                    Key::F => "f",
-                                    Key::G => "g",
-                                    Key::H => "h",
-                                    Key::I => "i",
-                                    Key::J => "j",
-                                    Key::K => "k",
-                                    Key::L => "l",
-                                    Key::M => "m",
-                                    Key::N => "n",
-                                    Key::O => "o",
-                                    Key::P => "p",
-                                    Key::Q => "q",
-                                    Key::R => "r",
-                                    Key::S => "s",
-                                    Key::T => "t",
-                                    Key::U => "u",
-                                    Key::V => "v",
-                                    Key::W => "w",
-                                    Key::X => "x",
-                                    Key::Y => "y",
-                                    Key::Z => "z",
-                                    Key::Up => "up",
-                                    Key::Down => "down",
-                                    Key::Left => "left",
-                                    Key::Right => "right",
-                                    Key::Space => "space",
-                                    Key::Tab => "tab",
-                                    Key::Enter => "enter",
-                                    Key::LeftShift => "leftshift",
-                                    Key::RightShift => "rightshift",
-                                    Key::Escape => "escape",
-                                    Key::Backspace => "backspace",
-                                    Key::Delete => "delete",
-                                    _ => "unknown",
-                                }
-                            }
-                            writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for keys pressed to be printed to it"), "{}", output);
-                        }
-                        _ => {
-                            panic!("unsupported usage of get command");
-                        }
-                    },
-                    "save" => {
-                        draw_target
-                            .write_png(command[1].clone())
-                            .expect("failed to save");
-                    }
-                    "strokeweight" => {
-                        stroke_weight = command[1]
-                            .parse::<f32>()
-                            .expect("expected stroke weight (f32)")
-                    }
-                    "strokecap" => {
-                        stroke_cap = match command[1].as_str() {
-                            "square" => LineCap::Square,
-                            "project" => LineCap::Butt,
-                            "round" => LineCap::Round,
-                            _ => panic!("expected either square, project, or round for stroke cap"),
-                        };
-                    }
-                    "strokejoin" => {
-                        stroke_join = match command[1].as_str() {
-                            "miter" => LineJoin::Miter,
-                            "bevel" => LineJoin::Bevel,
-                            "round" => LineJoin::Round,
-                            _ => panic!("expected either miter, bevel, or round for stroke join"),
-                        };
-                    }
-                    "rotate" => {
-                        if transformations.len() == 0 {
-                            panic!("invalid usage of push and pop");
-                        }
-                        *transformations.last_mut().unwrap() = transformations
-                            .last()
-                            .unwrap()
-                            .post_transform(&Transform::create_rotation(Angle::degrees(
-                                command[1].parse::<f32>().expect(
-                                    "expected rotation value in degrees (f32) for rotation",
-                                ),
-                            )));
-                        draw_target.set_transform(&transformations.last().unwrap());
-                    }
-                    "textfont" => {
-                        text_font = Some(
-                            Font::from_path(command[1].clone(), 0).expect("failed to load font"),
-                        );
-                    }
-                    "textsize" => {
-                        text_size = Some(
-                            command[1]
-                                .parse::<f32>()
-                                .expect("expected size of text (f32)"),
-                        );
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                3 => match command[0].as_str() {
-                    "get" => match command[1].as_str() {
-                        "mouseispressed" => match command[2].as_str() {
-                            "left" => {
-                                window.update();
-                                writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for boolean answer to \"is mouse pressed?\" to be printed to it"), "{}", window.get_mouse_down(MouseButton::Left));
-                            }
-                            "center" => {
-                                window.update();
-                                writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for boolean answer to \"is mouse pressed?\" to be printed to it"), "{}", window.get_mouse_down(MouseButton::Middle));
-                            }
-                            "right" => {
-                                window.update();
-                                writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for boolean answer to \"is mouse pressed?\" to be printed to it"), "{}", window.get_mouse_down(MouseButton::Right));
-                            }
-                            _ => panic!(
-                                "expected either left, center, or right mouse button ask if pressed"
-                            ),
-                        },
-                        "keyispressed" => {
-                            window.update();
-                            writeln!(process_in.as_mut().expect("process must be passed as argument to stdg for boolean answer to \"is key pressed?\" to be printed to it"), "{}", window.is_key_down(match command[2].as_str() {
-                                "0" => Key::Key0,
-                                "1" => Key::Key1,
-                                "2" => Key::Key2,
-                                "3" => Key::Key3,
-                                "4" => Key::Key4,
-                                "5" => Key::Key5,
-                                "6" => Key::Key6,
-                                "7" => Key::Key7,
-                                "8" => Key::Key8,
-                                "9" => Key::Key9,
-                                "a" => Key::A,
-                                "b" => Key::B,
-                                "c" => Key::C,
-                                "d" => Key::D,
-                                "e" => Key::E,
-                                "f" => Key::F,
-                                "g" => Key::G,
-                                "h" => Key::H,
-                                "i" => Key::I,
-                                "j" => Key::J,
-                                "k" => Key::K,
-                                "l" => Key::L,
-                                "m" => Key::M,
-                                "n" => Key::N,
-                                "o" => Key::O,
-                                "p" => Key::P,
-                                "q" => Key::Q,
-                                "r" => Key::R,
-                                "s" => Key::S,
-                                "t" => Key::T,
-                                "u" => Key::U,
-                                "v" => Key::V,
-                                "w" => Key::W,
-                                "x" => Key::X,
-                                "y" => Key::Y,
-                                "z" => Key::Z,
-                                "up" => Key::Up,
-                                "down" => Key::Down,
-                                "left" => Key::Left,
-                                "right" => Key::Right,
-                                "space" => Key::Space,
-                                "tab" => Key::Tab,
-                                "enter" => Key::Enter,
-                                "leftshift" => Key::LeftShift,
-                                "rightshift" => Key::RightShift,
-                                "escape" => Key::Escape,
-                                "backspace" => Key::Backspace,
-                                "delete" => Key::Delete,
-                                _ => panic!("unsupported key used")
-                            }));
-                        }
-                        _ => {
-                            panic!("unsupported usage of get command");
-                        }
-                    },
-                    "translate" => {
-                        if transformations.len() == 0 {
-                            panic!("invalid usage of push and pop");
-                        }
-                        *transformations.last_mut().unwrap() = transformations
-                            .last()
-                            .unwrap()
-                            .post_transform(&Transform::create_translation(
-                                command[1]
-                                    .parse::<f32>()
-                                    .expect("expected x value (f32) for translation"),
-                                command[2]
-                                    .parse::<f32>()
-                                    .expect("expected y value (f32) for translation"),
-                            ));
-                        draw_target.set_transform(&transformations.last().unwrap());
-                    }
-                    "scale" => {
-                        if transformations.len() == 0 {
-                            panic!("invalid usage of push and pop");
-                        }
-                        *transformations.last_mut().unwrap() = transformations
-                            .last()
-                            .unwrap()
-                            .post_transform(&Transform::create_scale(
-                                command[1]
-                                    .parse::<f32>()
-                                    .expect("expected x value (f32) for scaling"),
-                                command[2]
-                                    .parse::<f32>()
-                                    .expect("expected y value (f32) for scaling"),
-                            ));
-                        draw_target.set_transform(&transformations.last().unwrap());
-                    }
-                    "text" => {
-                        if let Some(fill_color) = fill {
-                            let source = Source::Solid(SolidSource {
-                                r: fill_color.0,
-                                g: fill_color.1,
-                                b: fill_color.2,
-                                a: 255,
-                            });
-
-                            let mut text_to_draw = String::new();
-                            if process.is_some() {
-                                process_out_reader
-                                    .as_mut()
-                                    .unwrap()
-                                    .read_line(&mut text_to_draw)
-                                    .expect("expected command to draw graphics");
-                            } else {
-                                stdin_reader
-                                    .as_mut()
-                                    .unwrap()
-                                    .read_line(&mut text_to_draw)
-                                    .expect("expected command to draw graphics");
-                            }
-                            draw_target.draw_text(
-                                &text_font
-                                    .clone()
-                                    .expect("text font must be given before text is drawn"),
-                                text_size.expect("text size must be given before text is drawn"),
-                                text_to_draw
-                                    .split_whitespace()
-                                    .collect::<Vec<&str>>()
-                                    .join(" ")
-                                    .as_str(),
-                                Point::new(
-                                    command[1]
-                                        .parse::<f32>()
-                                        .expect("expected x position of text (f32)"),
-                                    command[2]
-                                        .parse::<f32>()
-                                        .expect("expected y position of text (f32)"),
-                                ),
-                                &source,
-                                &DrawOptions {
-                                    alpha: fill_color.3 as f32 / 255.0,
-                                    ..DrawOptions::default()
-                                },
-                            );
-                        }
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                4 => match command[0].as_str() {
-                    "open" => {
-                        assert_eq!(command[2].as_str(), "as");
-                    	let decoder = png::Decoder::new(
-                            File::open(command[1].as_str()).expect("failed to open file"),
-                        );
-                        let (info, mut reader) = decoder.read_info().expect("failed to open file");
-                        let width = info.width;
-                        let height = info.height;
-                        let mut buf = vec![0; info.buffer_size()];
-                        reader.next_frame(&mut buf).unwrap();
-                        let buf_usize = buf
-                            .clone()
-                            .iter()
-                            .map(|byte| *byte as u32)
-                            .collect::<Vec<u32>>();
-                    	images.insert(command[3].clone(), (width, height, buf_usize));
-                    }
-                    "image" => {
-                    	draw_target.draw_image_at(
-                    		command[2]
-                                .parse::<f32>()
-                                .expect("expected x position (f32) of image"),
-                            command[3]
-                                .parse::<f32>()
-                                .expect("expected y position (f32) of image"),
-                            &Image {
-                            	width: images.get(&command[1]).expect("image not opened yet").0 as i32,
-                            	height: images.get(&command[1]).expect("image not opened yet").1 as i32,
-                            	data: &images.get(&command[1]).expect("image not opened yet").2,
-                            },
-                            &DrawOptions::default()
-                    	);
-                    }
-                    "background" => {
-                        draw_target.clear(SolidSource {
-                            r: command[1]
-                                .parse::<u8>()
-                                .expect("expected red value (u8) of color"),
-                            g: command[2]
-                                .parse::<u8>()
-                                .expect("expected green value (u8) of color"),
-                            b: command[3]
-                                .parse::<u8>()
-                                .expect("expected blue value (u8) of color"),
-                            a: 0,
-                        });
-                    }
-                    "fill" => {
-                        fill = Some((
-                            command[1]
-                                .parse::<u8>()
-                                .expect("expected red value (u8) of color"),
-                            command[2]
-                                .parse::<u8>()
-                                .expect("expected green value (u8) of color"),
-                            command[3]
-                                .parse::<u8>()
-                                .expect("expected blue value (u8) of color"),
-                            255,
-                        ));
-                    }
-                    "stroke" => {
-                        stroke = Some((
-                            command[1]
-                                .parse::<u8>()
-                                .expect("expected red value (u8) of color"),
-                            command[2]
-                                .parse::<u8>()
-                                .expect("expected green value (u8) of color"),
-                            command[3]
-                                .parse::<u8>()
-                                .expect("expected blue value (u8) of color"),
-                            255,
-                        ));
-                    }
-                    "circle" => {
-                        let x = command[1]
-                            .parse::<f32>()
-                            .expect("expected x position (f32) of circle");
-                        let y = command[2]
-                            .parse::<f32>()
-                            .expect("expected y position (f32) of circle");
-                        let r = command[3]
-                            .parse::<f32>()
-                            .expect("expected radius (f32) of circle");
-
-                        let mut pb = PathBuilder::new();
-                        pb.move_to(x + r, y);
-                        pb.arc(x, y, r, 0.0, 2.0 * std::f32::consts::PI);
-                        let path = pb.finish();
-
-                        if let Some(stroke_color) = &stroke {
-                            let source = Source::Solid(SolidSource {
-                                r: stroke_color.0,
-                                g: stroke_color.1,
-                                b: stroke_color.2,
-                                a: stroke_color.3,
-                            });
-                            draw_target.stroke(
-                                &path,
-                                &source,
-                                &StrokeStyle {
-                                    width: stroke_weight,
-                                    cap: stroke_cap,
-                                    join: stroke_join,
-                                    ..StrokeStyle::default()
-                                },
-                                &DrawOptions::default(),
-                            );
-                        }
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                5 => match command[0].as_str() {
-                    "ellipse" => {
-                        // get dimensions
-                        let x = command[1]
-                            .parse::<f32>()
-                            .expect("expected x position (f32) of ellipse");
-                        let y = command[2]
-                            .parse::<f32>()
-                            .expect("expected y position (f32) of ellipse");
-                        let w = command[3]
-                            .parse::<f32>()
-                            .expect("expected width (f32) of ellipse");
-                        let h = command[4]
-                            .parse::<f32>()
-                            .expect("expected height (f32) of ellipse");
-
-                        // push scaling transform
-                        transformations.push(transformations.last().unwrap().clone());
-
-                        // scale
-                        if transformations.len() == 0 {
-                            panic!("invalid usage of push and pop");
-                        }
-                        *transformations.last_mut().unwrap() = transformations
-                            .last()
-                            .unwrap()
-                            .post_transform(&Transform::create_scale(1.0, h / w));
-                        draw_target.set_transform(&transformations.last().unwrap());
-
-                        // draw the ellipse
-                        let mut pb = PathBuilder::new();
-                        pb.move_to(x + w / 2.0, y);
-                        pb.arc(x, y, w / 2.0, 0.0, 2.0 * std::f32::consts::PI);
-                        let path = pb.finish();
-
-                        if let Some(stroke_color) = &stroke {
-                            let source = Source::Solid(SolidSource {
-                                r: stroke_color.0,
-                                g: stroke_color.1,
-                                b: stroke_color.2,
-                                a: stroke_color.3,
-                            });
-                            draw_target.stroke(
-                                &path,
-                                &source,
-                                &StrokeStyle {
-                                    width: stroke_weight,
-                                    cap: stroke_cap,
-                                    join: stroke_join,
-                                    ..StrokeStyle::default()
-                                },
-                                &DrawOptions::default(),
-                            );
-                        }
-
-                        // pop scaling tansform
-                        transformations.pop();
-                        draw_target.set_transform(transformations.last().unwrap());
-                    }
-                    "line" => {
-                        let mut pb = PathBuilder::new();
-                        pb.move_to(
-                            command[1]
-                                .parse::<f32>()
-                                .expect("expected x position (f32) of rectangle"),
-                            command[2]
-                                .parse::<f32>()
-                                .expect("expected y position (f32) of rectangle"),
-                        );
-                        pb.line_to(
-                            command[3]
-                                .parse::<f32>()
-                                .expect("expected width (f32) of rectangle"),
-                            command[4]
-                                .parse::<f32>()
-                                .expect("expected height (f32) of rectangle"),
-                        );
-                        let path = pb.finish();
-
-                        if let Some(fill_color) = &fill {
-                            let source = Source::Solid(SolidSource {
-                                r: fill_color.0,
-                                g: fill_color.1,
-                                b: fill_color.2,
-                                a: 255,
-                            });
-                            draw_target.fill(
-                                &path,
-                                &source,
-                                &DrawOptions {
-                                    alpha: fill_color.3 as f32 / 255.0,
-                                    ..DrawOptions::default()
-                                },
-                            );
-                        }
-                        if let Some(stroke_color) = &stroke {
-                            let source = Source::Solid(SolidSource {
-                                r: stroke_color.0,
-                                g: stroke_color.1,
-                                b: stroke_color.2,
-                                a: stroke_color.3,
-                            });
-                            draw_target.stroke(
-                                &path,
-                                &source,
-                                &StrokeStyle {
-                                    width: stroke_weight,
-                                    cap: stroke_cap,
-                                    join: stroke_join,
-                                    ..StrokeStyle::default()
-                                },
-                                &DrawOptions::default(),
-                            );
-                        }
-                    }
-                    "rect" => {
-                        let mut pb = PathBuilder::new();
-                        pb.rect(
-                            command[1]
-                                .parse::<f32>()
-                                .expect("expected x position (f32) of rectangle"),
-                            command[2]
-                                .parse::<f32>()
-                                .expect("expected y position (f32) of rectangle"),
-                            command[3]
-                                .parse::<f32>()
-                                .expect("expected width (f32) of rectangle"),
-                            command[4]
-                                .parse::<f32>()
-                                .expect("expected height (f32) of rectangle"),
-                        );
-                        let path = pb.finish();
-
-                        if let Some(fill_color) = &fill {
-                            let source = Source::Solid(SolidSource {
-                                r: fill_color.0,
-                                g: fill_color.1,
-                                b: fill_color.2,
-                                a: 255,
-                            });
-                            draw_target.fill(
-                                &path,
-                                &source,
-                                &DrawOptions {
-                                    alpha: fill_color.3 as f32 / 255.0,
-                                    ..DrawOptions::default()
-                                },
-                            );
-                        }
-                        if let Some(stroke_color) = &stroke {
-                            let source = Source::Solid(SolidSource {
-                                r: stroke_color.0,
-                                g: stroke_color.1,
-                                b: stroke_color.2,
-                                a: stroke_color.3,
-                            });
-                            draw_target.stroke(
-                                &path,
-                                &source,
-                                &StrokeStyle {
-                                    width: stroke_weight,
-                                    cap: stroke_cap,
-                                    join: stroke_join,
-                                    ..StrokeStyle::default()
-                                },
-                                &DrawOptions::default(),
-                            );
-                        }
-                    }
-                    "fill" => {
-                        fill = Some((
-                            command[1]
-                                .parse::<u8>()
-                                .expect("expected red value (u8) of color"),
-                            command[2]
-                                .parse::<u8>()
-                                .expect("expected green value (u8) of color"),
-                            command[3]
-                                .parse::<u8>()
-                                .expect("expected blue value (u8) of color"),
-                            command[4]
-                                .parse::<u8>()
-                                .expect("expected alpha (transparency, u8) value of color"),
-                        ));
-                    }
-                    "stroke" => {
-                        stroke = Some((
-                            command[1]
-                                .parse::<u8>()
-                                .expect("expected red value (u8) of color"),
-                            command[2]
-                                .parse::<u8>()
-                                .expect("expected green value (u8) of color"),
-                            command[3]
-                                .parse::<u8>()
-                                .expect("expected blue value (u8) of color"),
-                            command[4]
-                                .parse::<u8>()
-                                .expect("expected alpha (transparency, u8) value of color"),
-                        ));
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                6 => match command[0].as_str() {
-                	"image" => {
-                    	draw_target.draw_image_with_size_at(
-                    		command[4]
-                                .parse::<f32>()
-                                .expect("expected width (f32) of image"),
-                            command[5]
-                                .parse::<f32>()
-                                .expect("expected height (f32) of image"),
-                    		command[2]
-                                .parse::<f32>()
-                                .expect("expected x position (f32) of image"),
-                            command[3]
-                                .parse::<f32>()
-                                .expect("expected y position (f32) of image"),
-                            &Image {
-                            	width: images.get(&command[1]).expect("image not opened yet").0 as i32,
-                            	height: images.get(&command[1]).expect("image not opened yet").1 as i32,
-                            	data: &images.get(&command[1]).expect("image not opened yet").2,
-                            },
-                            &DrawOptions::default()
-                    	);
-                    }
-                    "arc" => {
-                        let x = command[1]
-                            .parse::<f32>()
-                            .expect("expected x position (f32) of arc");
-                        let y = command[2]
-                            .parse::<f32>()
-                            .expect("expected y position (f32) of arc");
-                        let r = command[3]
-                            .parse::<f32>()
-                            .expect("expected radius (f32) of arc");
-                        let start_angle = command[4]
-                            .parse::<f32>()
-                            .expect("expected start angle (f32) of arc");
-                        let end_angle = command[5]
-                            .parse::<f32>()
-                            .expect("expected end angle (f32) of arc");
-
-                        let mut pb = PathBuilder::new();
-                        pb.move_to(x + r * start_angle.cos(), y + r * start_angle.sin());
-                        pb.arc(
-                            x,
-                            y,
-                            r,
-                            start_angle,
-                            2.0 * std::f32::consts::PI * end_angle / 360.0,
-                        );
-                        let path = pb.finish();
-
-                        if let Some(fill_color) = &fill {
-                            let source = Source::Solid(SolidSource {
-                                r: fill_color.0,
-                                g: fill_color.1,
-                                b: fill_color.2,
-                                a: 255,
-                            });
-                            draw_target.fill(
-                                &path,
-                                &source,
-                                &DrawOptions {
-                                    alpha: fill_color.3 as f32 / 255.0,
-                                    ..DrawOptions::default()
-                                },
-                            );
-                        }
-                        if let Some(stroke_color) = &stroke {
-                            let source = Source::Solid(SolidSource {
-                                r: stroke_color.0,
-                                g: stroke_color.1,
-                                b: stroke_color.2,
-                                a: stroke_color.3,
-                            });
-                            draw_target.stroke(
-                                &path,
-                                &source,
-                                &StrokeStyle {
-                                    width: stroke_weight,
-                                    cap: stroke_cap,
-                                    join: stroke_join,
-                                    ..StrokeStyle::default()
-                                },
-                                &DrawOptions::default(),
-                            );
-                        }
-                    }
-                    _ => {
-                        print!("{}", reading);
-                    }
-                },
-                _ => {
-                    print!("{}", reading);
+            Ok(Command::Print(text)) => println!("{}", text),
+            Ok(Command::Set(name, value)) => script.variables.set(&name, value),
+            Ok(Command::Repeat(count)) => script.repeat(count),
+            Ok(Command::For(name, from, to, step)) => {
+                if let Err(message) = script.for_loop(name, from, to, step) {
+                    client.error(&message);
+                }
+            }
+            Ok(Command::Define(name, arguments)) => script.define(name, arguments),
+            Ok(Command::End) if maybe_output => print!("{}", reading),
+            Ok(Command::End) => client.error("end without repeat, for or define"),
+            Ok(Command::Layer(name, z)) => {
+                if let Err(message) = display.layer(&name, z) {
+                    client.error(&message);
+                }
+            }
+            Ok(Command::Window(name, width, height, title)) => {
+                // a window that can't be made (like one that is too big)
+                // panics like a command that can't be drawn
+                let mut opened = Ok(());
+                draw(client, || {
+                    opened = windows.open(name, (width, height, &title), display, canvas)
+                });
+                if let Err(message) = opened {
+                    client.error(&message);
                 }
             }
+            Ok(Command::Select(name)) => match windows.select(&name, display, canvas) {
+                Ok(()) => {}
+                Err(_) if maybe_output => print!("{}", reading),
+                Err(message) => client.error(&message),
+            },
+            Ok(Command::Events(push)) => display.push(push),
+            Ok(Command::CloseCancel) => {
+                if client.cancel_close() {
+                    windows.reopen(display);
+                }
+            }
+            Ok(command) => draw(client, || canvas.execute(&command)),
+            // commands made with define are run and anything else that isn't
+            // a command is printed
+            //
+            // lines typed in are always meant as commands, so anything that
+            // isn't one is an error
+            Err(Error::Unknown) => match script.call(&command) {
+                Ok(true) => {}
+                Ok(false) if client.is_interactive() => {
+                    client.error(&format!("`{}` is not a command", command[0]))
+                }
+                Ok(false) => print!("{}", reading),
+                Err(message) => client.error(&message),
+            },
+            Err(Error::Arity(usage)) if client.is_interactive() => {
+                client.error(&format!("expected `{}`", usage))
+            }
+            Err(Error::Arity(_)) => print!("{}", reading),
+            Err(Error::Invalid(_)) if maybe_output && command::WORDS.contains(&&*command[0]) => {
+                print!("{}", reading)
+            }
+            Err(Error::Invalid(message)) => client.error(&message),
         }
 
         // clear the reading so that we can read the next line
@@ -1016,7 +468,100 @@ fn main() {
         reading.clear();
     }
 
-    if process.is_some() {
-        process.unwrap().kill().expect("failed to kill process");
+    windows.close(display, canvas);
+}
+
+// start and run a client without a window (like a script being tested or
+// run again whenever it changes) and get what it drew and the title of the
+// window it wanted
+pub fn run_headless(client: &mut Client) -> (Canvas, String) {
+    let (width, height, title) = start(client);
+    let mut canvas = Canvas::new(width, height);
+    run(client, &mut Display::Headless, &mut canvas);
+    (canvas, title)
+}
+
+// keep the windows responsive until the client has a line for us, which only
+// takes a while for lines typed into the repl
+//
+// returns false if the main window is closed while waiting
+fn wait(
+    client: &mut Client,
+    display: &mut Display,
+    canvas: &Canvas,
+    windows: &mut Windows,
+    text: bool,
+    collecting: bool,
+) -> bool {
+    while !client.is_ready(text, collecting) {
+        if !windows.is_open(display) {
+            return false;
+        }
+        display.present(canvas);
+        windows.update();
+        thread::sleep(Duration::from_millis(16));
+    }
+    true
+}
+
+// draw something on the canvas, which with --json (or in the repl) reports
+// anything wrong with it as an error instead of stopping
+fn draw(client: &mut Client, f: impl FnOnce()) {
+    if !client.keeps_going() {
+        return f();
+    }
+    if let Err(message) = catch(f) {
+        client.error(&message);
+    }
+}
+
+// run something that might panic, returning why it did
+//
+// the panic isn't printed, since whoever catches it says what went wrong
+// instead, but any other panic (on any thread) still is
+pub fn catch(f: impl FnOnce()) -> Result<(), String> {
+    thread_local! {
+        static CATCHING: Cell<bool> = const { Cell::new(false) };
+    }
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(|catching| catching.get()) {
+                hook(info);
+            }
+        }));
+    });
+
+    // catching inside of catching still catches
+    let was_catching = CATCHING.with(|catching| catching.replace(true));
+    let caught = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(was_catching));
+    caught.map_err(|panic| match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "command failed".to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches() {
+        assert_eq!(catch(|| {}), Ok(()));
+        assert_eq!(
+            catch(|| panic!("pop was not expected")),
+            Err("pop was not expected".to_string())
+        );
+        assert_eq!(
+            catch(|| panic!("expected {}", "end")),
+            Err("expected end".to_string())
+        );
+        // a panic caught inside of catch doesn't get to the outer one
+        assert_eq!(catch(|| assert!(catch(|| panic!()).is_err())), Ok(()));
     }
 }
@@ -0,0 +1,306 @@
+// for editing lines
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+// for etc.
+use std::borrow::Cow;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+use crate::client::Client;
+use crate::command::{COMMANDS, QUERIES};
+use crate::display::Display;
+
+// what can be asked for with get and the other words commands accept
+const ARGUMENTS: &[(&str, &[&str])] = &[
+    ("get", QUERIES),
+    ("mouseispressed", &["left", "center", "right"]),
+    ("mouseclicked", &["left", "center", "right"]),
+    ("mousedoubleclicked", &["left", "center", "right"]),
+    ("present", &["forever"]),
+    ("strokecap", &["square", "project", "round"]),
+    ("strokejoin", &["miter", "bevel", "round"]),
+];
+
+// stdg repl [width height [title]]
+//
+// open a window and draw each command as soon as it is typed in
+//
+// lines are read on their own thread and handled just like the lines printed
+// by any other client, while the window is kept responsive until the next
+// line is typed, so that errors and answers to get commands show up right
+// under the line
+pub fn main(args: &[String]) {
+    let width = args
+        .first()
+        .map(|width| {
+            width
+                .parse::<usize>()
+                .expect("expected integer (usize) width of window")
+        })
+        .unwrap_or(400);
+    let height = args
+        .get(1)
+        .map(|height| {
+            height
+                .parse::<usize>()
+                .expect("expected integer (usize) height of window")
+        })
+        .unwrap_or(400);
+    let title = match args.get(2..) {
+        Some(title) if !title.is_empty() => title.join(" "),
+        _ => "stdg".to_string(),
+    };
+
+    let mut display = Display::window(width, height, &title);
+    let mut canvas = Canvas::new(width, height);
+
+    println!("type help to list commands, press tab to complete a command");
+
+    let (lines_in, lines) = mpsc::channel::<Option<String>>();
+    let (prompts_in, prompts) = mpsc::channel::<&str>();
+    thread::spawn(move || {
+        let mut editor = Editor::<Keywords>::new();
+        editor.set_helper(Some(Keywords));
+        // we are told what to prompt for each time a line is wanted
+        while let Ok(prompt) = prompts.recv() {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    editor.add_history_entry(line.as_str());
+                    if lines_in.send(Some(line)).is_err() {
+                        break;
+                    }
+                }
+                // ctrl-c, ctrl-d or anything else that stops us from reading
+                Err(_) => {
+                    let _ = lines_in.send(None);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut client = Client::repl(Lines {
+        lines,
+        prompts: prompts_in,
+        next: None,
+        prompted: false,
+    });
+    crate::run(&mut client, &mut display, &mut canvas);
+}
+
+// the lines typed into the repl, which is where the client reads from
+pub struct Lines {
+    lines: mpsc::Receiver<Option<String>>,
+    prompts: mpsc::Sender<&'static str>,
+    // a line that was typed and hasn't been read yet, None once typing is
+    // done
+    next: Option<Option<String>>,
+    // whether a line was asked for and hasn't been typed yet
+    prompted: bool,
+}
+
+impl Lines {
+    // whether a line was typed, asking for one (with a prompt saying whether
+    // it's text or part of a block) if it hasn't been asked for yet
+    //
+    // lines that are only for the repl, like help, are handled here and
+    // another line is asked for
+    pub fn is_ready(&mut self, text: bool, collecting: bool) -> bool {
+        while self.next.is_none() {
+            if !self.prompted {
+                let prompt = if text {
+                    "text> "
+                } else if collecting {
+                    "...> "
+                } else {
+                    "stdg> "
+                };
+                // without anyone to type, there's nothing more to read
+                if self.prompts.send(prompt).is_err() {
+                    self.next = Some(None);
+                    break;
+                }
+                self.prompted = true;
+            }
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => None,
+            };
+            self.prompted = false;
+            match line {
+                Some(line) if !text && handle(&line) => {}
+                line => self.next = Some(line),
+            }
+        }
+        true
+    }
+
+    // the next line typed, waiting for it if it hasn't been typed yet
+    pub fn read_line(&mut self, buf: &mut String) -> usize {
+        while !self.is_ready(false, false) {
+            thread::sleep(Duration::from_millis(16));
+        }
+        match self.next.take().flatten() {
+            Some(line) => {
+                buf.push_str(&line);
+                buf.push('\n');
+                line.len() + 1
+            }
+            None => {
+                self.next = Some(None);
+                0
+            }
+        }
+    }
+}
+
+// handle a line that is only for the repl, returns false for any other line
+fn handle(line: &str) -> bool {
+    let command = line.split_whitespace().collect::<Vec<&str>>();
+    match command[..] {
+        ["help"] => {
+            for (_, usage) in COMMANDS.iter().filter(|(name, _)| *name != "start") {
+                println!("  {}", usage);
+            }
+        }
+        ["start", ..] => println!("error: the window is already started"),
+        ["present", "forever"] => {
+            println!("error: present forever would never let you type another command")
+        }
+        _ => return false,
+    }
+    true
+}
+
+// completes and hints commands as they are typed
+struct Keywords;
+
+impl Completer for Keywords {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let words = line[..start].split_whitespace().collect::<Vec<&str>>();
+        let word = &line[start..];
+
+        // the first word is a command, the rest depend on the word before
+        let candidates = match words.last() {
+            None => COMMANDS
+                .iter()
+                .map(|(name, _)| *name)
+                .filter(|name| *name != "start")
+                .chain(Some("help"))
+                .collect::<Vec<&str>>(),
+            Some(previous) => ARGUMENTS
+                .iter()
+                .find(|(name, _)| name == previous)
+                .map_or(vec![], |(_, arguments)| arguments.to_vec()),
+        };
+
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .filter(|candidate| candidate.starts_with(word))
+                .map(|candidate| Pair {
+                    display: candidate.to_string(),
+                    replacement: candidate.to_string() + " ",
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl Hinter for Keywords {
+    type Hint = String;
+
+    // once a whole command is typed, show how it's used
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let (_, usage) = COMMANDS
+            .iter()
+            .find(|(name, _)| Some(name) == words.first())?;
+        let usage = usage.split_whitespace().collect::<Vec<&str>>();
+        let rest = usage.get(words.len()..).filter(|rest| !rest.is_empty())?;
+        let space = if line.ends_with(char::is_whitespace) {
+            ""
+        } else {
+            " "
+        };
+        Some(format!("{}{}", space, rest.join(" ")))
+    }
+}
+
+impl Highlighter for Keywords {
+    // hints are dimmed so they don't look like they were typed
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+    }
+}
+
+impl Validator for Keywords {}
+
+impl Helper for Keywords {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let (lines_in, lines) = mpsc::channel();
+        let (prompts_in, prompts) = mpsc::channel();
+        let mut lines = Lines {
+            lines,
+            prompts: prompts_in,
+            next: None,
+            prompted: false,
+        };
+
+        // a line is asked for once until it is typed
+        assert!(!lines.is_ready(false, true));
+        assert!(!lines.is_ready(false, true));
+        assert_eq!(prompts.try_iter().collect::<Vec<&str>>(), ["...> "]);
+
+        // lines only for the repl are handled and another line is asked for
+        lines_in.send(Some("help".to_string())).unwrap();
+        assert!(!lines.is_ready(false, false));
+        assert_eq!(prompts.try_iter().collect::<Vec<&str>>(), ["stdg> "]);
+
+        lines_in.send(Some("rect 0 0 10 10".to_string())).unwrap();
+        assert!(lines.is_ready(true, false));
+        let mut line = String::new();
+        assert_eq!(lines.read_line(&mut line), 15);
+        assert_eq!(line, "rect 0 0 10 10\n");
+
+        // text to draw is never a line only for the repl
+        assert!(!lines.is_ready(true, false));
+        assert_eq!(prompts.try_iter().collect::<Vec<&str>>(), ["text> "]);
+        lines_in.send(Some("help".to_string())).unwrap();
+        assert!(lines.is_ready(true, false));
+        line.clear();
+        lines.read_line(&mut line);
+        assert_eq!(line, "help\n");
+
+        // once typing is done, there's nothing more to read
+        drop(lines_in);
+        assert!(lines.is_ready(false, false));
+        assert_eq!(lines.read_line(&mut line), 0);
+        assert_eq!(lines.read_line(&mut line), 0);
+    }
+}
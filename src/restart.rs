@@ -0,0 +1,245 @@
+// for etc.
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::canvas::Canvas;
+use crate::client::{Client, Launch};
+use crate::display::Display;
+use crate::input::Input;
+use crate::session::Recorder;
+
+// stdg --restart-on-exit ... and stdg --watch DIR ...
+//
+// the process is launched again (from scratch, with a new canvas) whenever it
+// exits or, with --watch, whenever something in DIR changes, and the window
+// is kept open the whole time unless the process asks for a different size
+//
+// without --restart-on-exit, a process that exits is only launched again once
+// something changes and until then what it drew last is presented
+pub fn main(
+    launch: &Launch,
+    configure: impl Fn(&mut Client),
+    recorder: Option<Recorder>,
+    on_exit: bool,
+    watch: Option<&str>,
+) -> i32 {
+    // only the first launch is recorded
+    let mut recorder = recorder;
+    let watcher = watch.map(Watcher::start);
+    let mut display: Option<Display> = None;
+    let mut canvas: Option<Canvas> = None;
+    loop {
+        let mut client = Client::spawn(launch);
+        configure(&mut client);
+        if let Some(recorder) = recorder.take() {
+            client.record(recorder);
+        }
+        if let Some(watcher) = &watcher {
+            watcher.kill_on_change(client.process());
+        }
+
+        // if the process panics us (like by printing something that isn't
+        // valid), we carry on like it exited
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+            let (width, height, title) = crate::start(&mut client);
+            display = Some(match display.take() {
+                Some(Display::Window { mut window, .. })
+                    if window.get_size() == (width, height) =>
+                {
+                    window.set_title(&title);
+                    let input = Input::default();
+                    input.listen(&mut window);
+                    Display::Window {
+                        window,
+                        title,
+                        input,
+                    }
+                }
+                _ => Display::window(width, height, &title),
+            });
+            canvas = Some(Canvas::new(width, height));
+            if let (Some(display), Some(canvas)) = (&mut display, &mut canvas) {
+                crate::run(&mut client, display, canvas);
+            }
+        }));
+        let code = client.close();
+
+        // closing the window is the only way to stop
+        if display.as_ref().is_some_and(|display| !display.is_open()) {
+            return code;
+        }
+        let exited = match ran {
+            Ok(()) => format!("process exited with code {}", code),
+            Err(_) => "process failed".to_string(),
+        };
+
+        match &watcher {
+            Some(watcher) if watcher.changed() => {
+                eprintln!("{} changed, restarting", watch.unwrap_or_default())
+            }
+            _ if on_exit => {
+                eprintln!("{}, restarting", exited);
+                // so that a process that keeps failing right away isn't
+                // launched as fast as it can be
+                thread::sleep(Duration::from_millis(250));
+            }
+            Some(watcher) => {
+                eprintln!("{}, waiting for changes", exited);
+                while !watcher.changed() {
+                    match (&mut display, &canvas) {
+                        (Some(display), Some(canvas)) if display.is_open() => {
+                            display.present(canvas)
+                        }
+                        (Some(_), _) => return code,
+                        _ => {}
+                    }
+                    thread::sleep(Duration::from_millis(16));
+                }
+                eprintln!("{} changed, restarting", watch.unwrap_or_default());
+            }
+            None => return code,
+        }
+    }
+}
+
+// keeps an eye on a directory on its own thread, killing the process that is
+// running whenever anything in it changes
+struct Watcher {
+    changed: Arc<AtomicBool>,
+    process: Arc<Mutex<Option<Arc<Mutex<Child>>>>>,
+}
+
+impl Watcher {
+    fn start(path: &str) -> Watcher {
+        let changed = Arc::new(AtomicBool::new(false));
+        let process: Arc<Mutex<Option<Arc<Mutex<Child>>>>> = Arc::new(Mutex::new(None));
+        let watcher = Watcher {
+            changed: changed.clone(),
+            process: process.clone(),
+        };
+
+        let mut changes = Changes::new(&[PathBuf::from(path)]);
+        thread::spawn(move || {
+            // check for changes a few times a second
+            loop {
+                thread::sleep(Duration::from_millis(250));
+                if changes.changed() {
+                    changed.store(true, Ordering::SeqCst);
+                    if let Some(process) = process.lock().unwrap().take() {
+                        let _ = process.lock().unwrap().kill();
+                    }
+                }
+            }
+        });
+        watcher
+    }
+
+    // the process to kill when something changes
+    fn kill_on_change(&self, process: Option<Arc<Mutex<Child>>>) {
+        *self.process.lock().unwrap() = process;
+    }
+
+    // whether anything changed since this was last asked
+    fn changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
+
+// the files and directories being watched for changes and when each was
+// last changed
+pub struct Changes {
+    watching: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Changes {
+    pub fn new(paths: &[PathBuf]) -> Changes {
+        let mut changes = Changes { watching: vec![] };
+        changes.watch(paths);
+        changes
+    }
+
+    // watch more files or directories, from how they are now
+    pub fn watch(&mut self, paths: &[PathBuf]) {
+        for path in paths {
+            if !self.watching.iter().any(|(watching, _)| watching == path) {
+                self.watching.push((path.clone(), last_modified(path)));
+            }
+        }
+    }
+
+    // whether anything changed since this was last asked
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, modified) in &mut self.watching {
+            let last_modified = last_modified(path);
+            if last_modified != *modified {
+                *modified = last_modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+// when anything in a directory (or a file) was last changed
+//
+// a directory is changed when something is added to it or removed from it,
+// so this is the latest of everything in it and itself
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::metadata(path).ok()?;
+    let mut modified = metadata.modified().ok();
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).ok()?.flatten() {
+            modified = modified.max(last_modified(&entry.path()));
+        }
+    }
+    modified
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn changes() {
+        let dir = std::env::temp_dir().join("stdg-restart-changes");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (script, included) = (dir.join("script.txt"), dir.join("included.txt"));
+        fs::write(&script, "").unwrap();
+        fs::write(&included, "").unwrap();
+        let touch = |path: &Path, seconds: u64| {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap()
+        };
+
+        let mut changes = Changes::new(std::slice::from_ref(&script));
+        assert!(!changes.changed());
+        touch(&script, 1);
+        assert!(changes.changed());
+        assert!(!changes.changed());
+
+        // files watched later are watched from how they are then
+        touch(&included, 1);
+        changes.watch(&[script.clone(), included.clone()]);
+        assert!(!changes.changed());
+        touch(&included, 2);
+        assert!(changes.changed());
+
+        // a directory changes when anything in it does
+        let mut changes = Changes::new(std::slice::from_ref(&dir));
+        touch(&script, u64::from(u32::MAX));
+        assert!(changes.changed());
+    }
+}
@@ -0,0 +1,84 @@
+// for displaying window
+use minifb::Window;
+// for etc.
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::canvas::Canvas;
+use crate::client::Client;
+use crate::display::Display;
+use crate::restart::Changes;
+
+// stdg run FILE [--watch]
+//
+// run a script of commands, the same as piping it into stdg
+//
+// with --watch, the script is run again from scratch whenever it (or a file
+// it includes) changes so that you can see what you're drawing as you write
+// it
+pub fn main(args: &[String]) {
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .expect("expected script to run");
+    let watch = args.iter().any(|arg| arg == "--watch");
+
+    if !watch {
+        let mut client = Client::file(path);
+        let (width, height, title) = crate::start(&mut client);
+        let mut display = Display::window(width, height, &title);
+        let mut canvas = Canvas::new(width, height);
+        crate::run(&mut client, &mut display, &mut canvas);
+        return;
+    }
+
+    let mut window: Option<Window> = None;
+    let mut canvas: Option<Canvas> = None;
+    let mut changes: Option<Changes> = None;
+    loop {
+        // check for changes (to the script or any file it includes) a few
+        // times a second
+        if changes.as_mut().is_none_or(|changes| changes.changed()) {
+            let mut watching = Changes::new(&[PathBuf::from(path)]);
+
+            // the script is run without a window and what it finally draws
+            // is what we present
+            // if it panics, we keep presenting what it drew last time
+            let mut client = Client::file(path);
+            let ran = panic::catch_unwind(AssertUnwindSafe(|| crate::run_headless(&mut client)));
+            watching.watch(client.included());
+            changes = Some(watching);
+            match ran {
+                Ok((drawn, title)) => {
+                    let drawn_size = size(&drawn);
+                    match &mut window {
+                        Some(window) if canvas.as_ref().map(size) == Some(drawn_size) => {
+                            window.set_title(&title)
+                        }
+                        _ => window = Some(crate::open_window(drawn_size.0, drawn_size.1, &title)),
+                    }
+                    canvas = Some(drawn);
+                }
+                Err(_) => eprintln!("failed to run {}, waiting for it to change", path),
+            }
+        }
+
+        if let (Some(window), Some(canvas)) = (&mut window, &canvas) {
+            if !window.is_open() {
+                break;
+            }
+            window.update_with_buffer(canvas.data()).unwrap();
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// the width and height of a canvas
+fn size(canvas: &Canvas) -> (usize, usize) {
+    (
+        canvas.draw_target.width() as usize,
+        canvas.draw_target.height() as usize,
+    )
+}
@@ -0,0 +1,406 @@
+// for etc.
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::command;
+use crate::expression::Variables;
+
+// a block of lines started by repeat, for or define that is being collected
+// until its end
+enum Block {
+    Repeat(usize),
+    For(String, f32, f32, f32),
+    Define(String, Vec<String>),
+}
+
+// how many commands made with define can run inside each other, so that one
+// that runs itself doesn't run forever
+pub const MAX_CALLS: usize = 64;
+
+// what happens once the lines of a frame have all been handled, which is
+// going around a loop again or ending a command made with define
+enum Again {
+    // how many more times around
+    Repeat(usize),
+    // the variable, its value next time around, where it stops and the step
+    For(String, f32, f32, f32),
+    Return,
+}
+
+// lines being handled and which one is next
+//
+// a loop only ever has the lines of one time around it pending, so a loop
+// that runs millions of times doesn't need millions of lines
+struct Frame {
+    lines: Rc<[String]>,
+    next: usize,
+    again: Again,
+}
+
+// the state of a script that isn't drawing state, like variables, blocks and
+// defined commands
+//
+// blocks are run by collecting their lines until they end and then handling
+// those lines (once for each time around the loop) before anything else is
+// read, so loops and defined commands end up as the commands we already
+// know how to handle
+pub struct Script {
+    pub variables: Variables,
+    // the blocks being run, the innermost last
+    pending: Vec<Frame>,
+    // the argument names and lines of commands made with define
+    defines: HashMap<String, (Vec<String>, Rc<[String]>)>,
+    // the block being collected, its lines so far and how many blocks inside
+    // of it haven't ended yet
+    collecting: Option<(Block, Vec<String>, usize)>,
+    // whether the next line collected is text to draw, which is never the
+    // end of a block
+    text: bool,
+    // how many commands made with define are running inside each other
+    calls: usize,
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script {
+            variables: Variables::new(),
+            pending: vec![],
+            defines: HashMap::new(),
+            collecting: None,
+            text: false,
+            calls: 0,
+        }
+    }
+
+    // read the next line to handle, which is the next line of a block being
+    // run or else a line read with the given function
+    pub fn read_line(
+        &mut self,
+        buf: &mut String,
+        read: impl FnOnce(&mut String) -> usize,
+    ) -> usize {
+        while let Some(frame) = self.pending.last_mut() {
+            if let Some(line) = frame.lines.get(frame.next) {
+                frame.next += 1;
+                buf.push_str(line);
+                return line.len();
+            }
+
+            // go around again or else this block is done
+            frame.next = 0;
+            match &mut frame.again {
+                Again::Repeat(left) if *left > 0 => *left -= 1,
+                Again::For(name, value, to, step) if goes_on(*value, *to, *step) => {
+                    self.variables.set(name, *value);
+                    *value += *step;
+                }
+                Again::Return => {
+                    self.calls -= 1;
+                    self.pending.pop();
+                }
+                _ => {
+                    self.pending.pop();
+                }
+            }
+        }
+        read(buf)
+    }
+
+    // whether a block is being collected and hasn't ended yet
+    pub fn is_collecting(&self) -> bool {
+        self.collecting.is_some()
+    }
+
+    // collect a line (split into words) if a block is being collected,
+    // returns whether it was collected
+    //
+    // the block is run once it ends
+    pub fn collect(&mut self, line: &str, command: &[String]) -> bool {
+        let (lines, depth) = match &mut self.collecting {
+            Some((_, lines, depth)) => (lines, depth),
+            None => return false,
+        };
+
+        let mut line = line.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        if self.text {
+            self.text = false;
+            lines.push(line);
+            return true;
+        }
+
+        match (command.first().map(|name| name.as_str()), command.len()) {
+            (Some("repeat"), _) | (Some("for"), _) | (Some("define"), _) => *depth += 1,
+            (Some("end"), 1) if *depth == 0 => {
+                let (block, lines, _) = self.collecting.take().unwrap();
+                self.finish(block, lines);
+                return true;
+            }
+            (Some("end"), 1) => *depth -= 1,
+            (Some("text"), 3) => self.text = true,
+            _ => {}
+        }
+        lines.push(line);
+        true
+    }
+
+    // start collecting the lines of a repeat block
+    pub fn repeat(&mut self, count: usize) {
+        self.collecting = Some((Block::Repeat(count), vec![], 0));
+    }
+
+    // start collecting the lines of a for block, which counts from one value
+    // up to (but not including) another
+    pub fn for_loop(
+        &mut self,
+        name: String,
+        from: f32,
+        to: f32,
+        step: Option<f32>,
+    ) -> Result<(), String> {
+        let step = step.unwrap_or(if from <= to { 1.0 } else { -1.0 });
+        if step == 0.0 || (to - from) / step < 0.0 {
+            return Err(format!(
+                "expected step of for to count from {} towards {}",
+                from, to
+            ));
+        }
+        self.collecting = Some((Block::For(name, from, to, step), vec![], 0));
+        Ok(())
+    }
+
+    // start collecting the lines of a command being defined
+    pub fn define(&mut self, name: String, arguments: Vec<String>) {
+        self.collecting = Some((Block::Define(name, arguments), vec![], 0));
+    }
+
+    // whether the given word is a command made with define
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    // replace the arguments of a command (one we know or one made with
+    // define) with their values
+    //
+    // every argument of a command made with define is a number
+    pub fn expand(&self, command: &[String]) -> Result<Vec<String>, String> {
+        match command.first() {
+            Some(name) if command::is_command(name) => self
+                .variables
+                .expand(command, |position| command::is_number(name, position)),
+            Some(name) if self.is_defined(name) => self.variables.expand(command, |_| true),
+            _ => Ok(command.to_vec()),
+        }
+    }
+
+    // run a command made with define, returns false if the command wasn't
+    // made with define
+    //
+    // the arguments are set as variables for the lines of the command
+    pub fn call(&mut self, command: &[String]) -> Result<bool, String> {
+        let (arguments, lines) = match command.first().and_then(|name| self.defines.get(name)) {
+            Some(define) => define,
+            None => return Ok(false),
+        };
+        if arguments.len() != command.len() - 1 {
+            return Err(format!(
+                "expected {} arguments to {} but got {}",
+                arguments.len(),
+                command[0],
+                command.len() - 1
+            ));
+        }
+        if self.calls >= MAX_CALLS {
+            return Err(format!(
+                "failed to run {}, commands made with define can only run {} deep",
+                command[0], MAX_CALLS
+            ));
+        }
+
+        let mut values = vec![];
+        for (argument, value) in arguments.iter().zip(&command[1..]) {
+            let value = value
+                .parse::<f32>()
+                .map_err(|_| format!("expected value (f32) of argument {}", argument))?;
+            values.push((argument.clone(), value));
+        }
+        let lines = lines.clone();
+        for (argument, value) in values {
+            self.variables.set(&argument, value);
+        }
+        self.calls += 1;
+        self.pending.push(Frame {
+            lines,
+            next: 0,
+            again: Again::Return,
+        });
+        Ok(true)
+    }
+
+    // a block has ended, so run it (or remember it if it's a define)
+    //
+    // the lines of a loop are handled once now and again each time they run
+    // out, until the loop is done
+    fn finish(&mut self, block: Block, lines: Vec<String>) {
+        let again = match block {
+            Block::Repeat(count) if count > 0 => Again::Repeat(count - 1),
+            Block::For(name, from, to, step) if goes_on(from, to, step) => {
+                self.variables.set(&name, from);
+                Again::For(name, from + step, to, step)
+            }
+            Block::Define(name, arguments) => {
+                self.defines.insert(name, (arguments, lines.into()));
+                return;
+            }
+            _ => return,
+        };
+        // a loop with no lines does nothing however many times it runs
+        if !lines.is_empty() {
+            self.pending.push(Frame {
+                lines: lines.into(),
+                next: 0,
+                again,
+            });
+        }
+    }
+}
+
+// whether a for loop that has got to the given value runs again
+fn goes_on(value: f32, to: f32, step: f32) -> bool {
+    (step > 0.0 && value < to) || (step < 0.0 && value > to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression;
+    use std::collections::VecDeque;
+
+    // handle lines like the run loop does with blocks and defines, returning
+    // the lines that come out the other end
+    fn run(script: &mut Script, lines: &[&str]) -> Result<Vec<String>, String> {
+        run_until(script, lines, usize::MAX)
+    }
+
+    // handle lines until the given number of lines have come out
+    fn run_until(script: &mut Script, lines: &[&str], most: usize) -> Result<Vec<String>, String> {
+        let mut lines: VecDeque<&str> = lines.iter().copied().collect();
+        let mut ran = vec![];
+        while ran.len() < most {
+            let mut line = String::new();
+            let read = script.read_line(&mut line, |line| match lines.pop_front() {
+                Some(next) => {
+                    line.push_str(next);
+                    next.len()
+                }
+                None => 0,
+            });
+            if read == 0 {
+                return Ok(ran);
+            }
+            let command = expression::words(&line);
+            if script.collect(&line, &command) {
+                continue;
+            }
+            let command = script.expand(&command)?;
+            match command.first().map(|name| name.as_str()) {
+                Some("repeat") => script.repeat(command[1].parse().unwrap()),
+                Some("for") => script.for_loop(
+                    command[1].clone(),
+                    command[2].parse().unwrap(),
+                    command[3].parse().unwrap(),
+                    command.get(4).map(|step| step.parse().unwrap()),
+                )?,
+                Some("define") => script.define(command[1].clone(), command[2..].to_vec()),
+                _ if script.call(&command)? => {}
+                _ => ran.push(command.join(" ")),
+            }
+        }
+        Ok(ran)
+    }
+
+    #[test]
+    fn blocks() {
+        let mut script = Script::new();
+        let ran = run(
+            &mut script,
+            &[
+                "define dot x",
+                "circle $x 0 1",
+                "end",
+                "for i 0 3",
+                "repeat 2",
+                "dot ($i * 10)",
+                "end",
+                "end",
+            ],
+        );
+        assert_eq!(
+            ran.unwrap(),
+            [0, 0, 10, 10, 20, 20]
+                .iter()
+                .map(|x| format!("circle {} 0 1", x))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn for_step() {
+        let mut script = Script::new();
+        let ran = run(&mut script, &["for i 3 0", "line $i", "end"]);
+        assert_eq!(ran.unwrap(), ["line 3", "line 2", "line 1"]);
+        assert_eq!(
+            script.for_loop("i".to_string(), 0.0, 10.0, Some(-1.0)),
+            Err("expected step of for to count from 0 towards 10".to_string())
+        );
+        assert!(script
+            .for_loop("i".to_string(), 0.0, 10.0, Some(0.0))
+            .is_err());
+    }
+
+    #[test]
+    fn calls() {
+        let mut script = Script::new();
+        assert_eq!(
+            run(&mut script, &["define f", "f", "end", "f"]),
+            Err(format!(
+                "failed to run f, commands made with define can only run {} deep",
+                MAX_CALLS
+            ))
+        );
+
+        // commands that are done running don't count
+        let mut script = Script::new();
+        let mut lines = vec!["define f", "line", "end"];
+        lines.extend(std::iter::repeat_n("f", MAX_CALLS * 2));
+        assert_eq!(run(&mut script, &lines).unwrap().len(), MAX_CALLS * 2);
+    }
+
+    #[test]
+    fn big_loops() {
+        // only the lines of one time around a loop are kept however many
+        // times it runs
+        let mut script = Script::new();
+        let ran = run_until(
+            &mut script,
+            &[
+                "repeat 30000000",
+                "for i 0 1000000000",
+                "nofill",
+                "end",
+                "end",
+            ],
+            100000,
+        );
+        assert!(ran.unwrap().iter().all(|line| line == "nofill"));
+        let lines: Vec<usize> = script
+            .pending
+            .iter()
+            .map(|frame| frame.lines.len())
+            .collect();
+        assert_eq!(lines, [3, 1]);
+        assert_eq!(script.variables.eval("$i"), Ok(99999.0));
+    }
+}
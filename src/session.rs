@@ -0,0 +1,160 @@
+// for etc.
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+// a session log is a plain text file with one line per event
+//
+// 0.001202 < start 400 400 A Polygon
+// 0.001536 < get mousex
+// 0.018170 > 121
+//
+// where the first column is the number of seconds since the session started,
+// < is a line read from the client, > is a reply sent to the client and ! is
+// an event sent to the client without it asking (like close)
+//
+// because the replies are in the log, a session can be replayed without the
+// client and without a window and it will draw exactly the same thing, and
+// events are only there to read since what the client did about them is in
+// the lines read from it
+
+// writes every line read and every reply sent to a session log
+pub struct Recorder {
+    file: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Recorder {
+        Recorder {
+            file: BufWriter::new(File::create(path).expect("failed to create session log")),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn input(&mut self, line: &str) {
+        self.write('<', line);
+    }
+
+    pub fn reply(&mut self, line: &str) {
+        self.write('>', line);
+    }
+
+    pub fn event(&mut self, line: &str) {
+        self.write('!', line);
+    }
+
+    fn write(&mut self, direction: char, line: &str) {
+        writeln!(
+            self.file,
+            "{:.6} {} {}",
+            self.start.elapsed().as_secs_f64(),
+            direction,
+            line.trim_end_matches(&['\r', '\n'][..])
+        )
+        .expect("failed to write to session log");
+        // flush as we go so that the log is complete even if we panic
+        self.file.flush().expect("failed to write to session log");
+    }
+}
+
+// the lines and replies of a recorded session, in the order they happened
+pub struct Replay {
+    inputs: VecDeque<String>,
+    replies: VecDeque<String>,
+}
+
+impl Replay {
+    pub fn open(path: &str) -> Replay {
+        let mut inputs = VecDeque::new();
+        let mut replies = VecDeque::new();
+
+        for event in fs::read_to_string(path)
+            .expect("failed to read session log")
+            .lines()
+        {
+            let mut fields = event.splitn(3, ' ');
+            let _time = fields.next();
+            let direction = fields.next().expect("expected <, > or ! in session log");
+            let line = fields.next().unwrap_or("").to_string();
+            match direction {
+                "<" => inputs.push_back(line),
+                ">" => replies.push_back(line),
+                "!" => {}
+                _ => panic!("expected <, > or ! in session log"),
+            }
+        }
+
+        Replay { inputs, replies }
+    }
+
+    // the next line the client printed, or None once the session is over
+    pub fn next_input(&mut self) -> Option<String> {
+        self.inputs.pop_front()
+    }
+
+    // the reply that was sent for the next get command
+    pub fn next_reply(&mut self) -> String {
+        self.replies
+            .pop_front()
+            .expect("session log has no reply for get command")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+
+    use crate::client::Client;
+
+    // run a client to the end without a window, returning what it drew
+    fn draw(mut client: Client, log: &str) -> Vec<u32> {
+        client.record(Recorder::create(log));
+        crate::run_headless(&mut client).0.data().to_vec()
+    }
+
+    // the lines of a session log without the times
+    fn lines(log: &str) -> Vec<String> {
+        fs::read_to_string(log)
+            .unwrap()
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn replay_draws_the_same() {
+        let dir = std::env::temp_dir();
+        let recorded = dir.join("stdg-session-recorded.log");
+        let replayed = dir.join("stdg-session-replayed.log");
+        let (recorded, replayed) = (recorded.to_str().unwrap(), replayed.to_str().unwrap());
+
+        let script = "start 40 30 Session\n\
+                      background 255 255 255\n\
+                      get version\n\
+                      set x 5\n\
+                      fill 255 0 0\n\
+                      for i 0 3\n\
+                      rect ($x + $i * 10) 5 8 8\n\
+                      end\n\
+                      stroke 0 0 255\n\
+                      line 0 29 39 0\n";
+        let client = Client::stream(Box::new(io::sink()), Box::new(Cursor::new(script)));
+        let drawn = draw(client, recorded);
+        assert!(drawn.iter().any(|pixel| pixel & 0xffffff == 0xff0000));
+
+        let client = Client::replay(Replay::open(recorded));
+        assert!(drawn == draw(client, replayed));
+        assert_eq!(lines(recorded), lines(replayed));
+        assert!(lines(recorded).contains(&format!("> {}", crate::command::VERSION)));
+        assert_eq!(
+            lines(recorded)
+                .iter()
+                .filter(|line| line.starts_with('>'))
+                .count(),
+            1
+        );
+    }
+}
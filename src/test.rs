@@ -0,0 +1,184 @@
+// for etc.
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::canvas::Canvas;
+use crate::client::Client;
+
+// stdg test DIR [--tolerance N] [--update]
+//
+// every .txt file in the directory is a script of commands that is run
+// without a window, and what it finally draws is compared to the .png file
+// with the same name
+//
+// a pixel matches if none of its red-green-blue-alpha values are off by more
+// than the tolerance (0 by default)
+//
+// when a script fails, we save what it actually drew to NAME.actual.png and
+// the pixels that didn't match (in red) to NAME.diff.png
+//
+// with --update, we don't compare anything and just save what each script
+// draws as its .png
+pub fn main(args: &[String]) {
+    let mut dir = None;
+    let mut tolerance = 0;
+    let mut update = false;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tolerance" => {
+                tolerance = args
+                    .get(i + 1)
+                    .expect("expected tolerance")
+                    .parse::<u8>()
+                    .expect("expected tolerance (u8) of pixel values");
+                i += 1;
+            }
+            "--update" => update = true,
+            _ => dir = Some(args[i].clone()),
+        }
+        i += 1;
+    }
+    let dir = dir.expect("expected directory of scripts to test");
+
+    let mut scripts = fs::read_dir(&dir)
+        .expect("failed to read directory of scripts")
+        .map(|entry| entry.expect("failed to read directory of scripts").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect::<Vec<PathBuf>>();
+    scripts.sort();
+
+    let mut failed = 0;
+    for script in &scripts {
+        let expected = script.with_extension("png");
+
+        // a script that panics just fails, the rest still get tested
+        let canvas = match panic::catch_unwind(AssertUnwindSafe(|| draw(script))) {
+            Ok(canvas) => canvas,
+            Err(_) => {
+                println!("FAIL {} (panicked)", script.display());
+                failed += 1;
+                continue;
+            }
+        };
+
+        if update {
+            canvas
+                .draw_target
+                .write_png(&expected)
+                .expect("failed to save");
+            println!("saved {}", expected.display());
+            continue;
+        }
+
+        match compare(&canvas, &expected, tolerance) {
+            Ok(()) => println!("ok   {}", script.display()),
+            Err(reason) => {
+                println!("FAIL {} ({})", script.display(), reason);
+                failed += 1;
+            }
+        }
+    }
+
+    if !update {
+        println!("{} passed, {} failed", scripts.len() - failed, failed);
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// run a script without a window and get what it drew
+fn draw(script: &Path) -> Canvas {
+    let mut client = Client::file(script.to_str().expect("expected path to be valid text"));
+    crate::run_headless(&mut client).0
+}
+
+// compare what was drawn to the expected image, saving the actual and diff
+// images if they don't match
+fn compare(canvas: &Canvas, expected: &Path, tolerance: u8) -> Result<(), String> {
+    let actual_path = expected.with_extension("actual.png");
+    let diff_path = expected.with_extension("diff.png");
+    let width = canvas.draw_target.width() as u32;
+    let height = canvas.draw_target.height() as u32;
+    let actual = canvas.rgba();
+
+    let file = match File::open(expected) {
+        Ok(file) => file,
+        Err(_) => {
+            canvas
+                .draw_target
+                .write_png(&actual_path)
+                .expect("failed to save");
+            return Err(format!("missing {}", expected.display()));
+        }
+    };
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let (info, mut reader) = decoder.read_info().expect("failed to open file");
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).expect("failed to open file");
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!(
+            "{} must be an 8-bit RGBA image",
+            expected.display()
+        ));
+    }
+
+    if info.width != width || info.height != height {
+        canvas
+            .draw_target
+            .write_png(&actual_path)
+            .expect("failed to save");
+        return Err(format!(
+            "expected {}x{} but drew {}x{}",
+            info.width, info.height, width, height
+        ));
+    }
+
+    // mismatched pixels are red, everything else is a faded copy of the
+    // expected image
+    let mut diff = Vec::with_capacity(actual.len());
+    let mut mismatched = 0;
+    for (actual, expected) in actual.chunks(4).zip(buf.chunks(4)) {
+        let matches = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| (*a as i32 - *e as i32).abs() <= tolerance as i32);
+        if matches {
+            diff.extend_from_slice(&[expected[0], expected[1], expected[2], expected[3] / 4]);
+        } else {
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+            mismatched += 1;
+        }
+    }
+
+    if mismatched == 0 {
+        return Ok(());
+    }
+
+    canvas
+        .draw_target
+        .write_png(&actual_path)
+        .expect("failed to save");
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(&diff_path).expect("failed to save")),
+        width,
+        height,
+    );
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&diff))
+        .expect("failed to save");
+
+    Err(format!(
+        "{} of {} pixels differ, see {}",
+        mismatched,
+        width * height,
+        diff_path.display()
+    ))
+}
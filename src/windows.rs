@@ -0,0 +1,116 @@
+// for etc.
+use std::mem;
+
+use crate::canvas::Canvas;
+use crate::display::Display;
+
+// the window opened by start, which is what the other windows are opened
+// from and which stdg is done with once it is closed
+pub const MAIN: &str = "main";
+
+// the windows a client opened with window NAME W H TITLE
+//
+// the selected window is the display and canvas that commands are handled
+// with, so selecting a window swaps it with the one that was selected and
+// everything else (drawing, presenting, get commands) goes to it without
+// knowing there are other windows
+pub struct Windows {
+    selected: String,
+    // every window other than the selected one
+    others: Vec<(String, Display, Canvas)>,
+}
+
+impl Windows {
+    pub fn new() -> Windows {
+        Windows {
+            selected: MAIN.to_string(),
+            others: vec![],
+        }
+    }
+
+    // whether the main window is still open, closing any other window just
+    // leaves it closed
+    pub fn is_open(&self, display: &Display) -> bool {
+        match self.others.iter().find(|(name, ..)| name == MAIN) {
+            Some((_, main, _)) => main.is_open(),
+            None => display.is_open(),
+        }
+    }
+
+    // open the main window again after it was closed
+    pub fn reopen(&mut self, display: &mut Display) {
+        match self.others.iter_mut().find(|(name, ..)| name == MAIN) {
+            Some((_, main, _)) => main.reopen(),
+            None => display.reopen(),
+        }
+    }
+
+    // open a window and select it
+    pub fn open(
+        &mut self,
+        name: String,
+        (width, height, title): (usize, usize, &str),
+        display: &mut Display,
+        canvas: &mut Canvas,
+    ) -> Result<(), String> {
+        if name == self.selected || self.others.iter().any(|(other, ..)| *other == name) {
+            return Err(format!("window {} is already open", name));
+        }
+        let opened = display.open(width, height, title)?;
+        self.others.push((
+            mem::replace(&mut self.selected, name),
+            mem::replace(display, opened),
+            mem::replace(canvas, Canvas::new(width, height)),
+        ));
+        Ok(())
+    }
+
+    // select a window that was opened before
+    pub fn select(
+        &mut self,
+        name: &str,
+        display: &mut Display,
+        canvas: &mut Canvas,
+    ) -> Result<(), String> {
+        if name == self.selected {
+            return Ok(());
+        }
+        let other = match self.others.iter_mut().find(|(other, ..)| other == name) {
+            Some(other) => other,
+            None => return Err(format!("window {} isn't open", name)),
+        };
+        mem::swap(&mut self.selected, &mut other.0);
+        mem::swap(display, &mut other.1);
+        mem::swap(canvas, &mut other.2);
+        Ok(())
+    }
+
+    // update every window other than the selected one, which is done
+    // whenever the selected one is presented
+    pub fn update(&mut self) {
+        for (_, other, _) in &mut self.others {
+            other.update();
+        }
+    }
+
+    // present every window until the main window is closed
+    pub fn present_forever(&mut self, display: &mut Display, canvas: &Canvas) {
+        // without any other windows (or without windows at all), there's
+        // nothing to keep presenting but the selected display
+        if self.others.is_empty() || !display.is_window() {
+            return display.present_forever(canvas);
+        }
+        while self.is_open(display) {
+            display.present(canvas);
+            for (_, other, canvas) in &mut self.others {
+                other.present(canvas);
+            }
+        }
+    }
+
+    // select the main window again and close the others, so that whoever
+    // ran the client is left with what was drawn in the main window
+    pub fn close(mut self, display: &mut Display, canvas: &mut Canvas) {
+        let _ = self.select(MAIN, display, canvas);
+    }
+}